@@ -0,0 +1,89 @@
+//! Launch velocities for bodies that only feel gravity.
+//!
+//! The solutions account for the fixed time step the physics integrates with,
+//! so a body launched with them ends up exactly where it was aimed, not just close to it.
+
+use bevy::prelude::*;
+
+use crate::SUB_DT;
+
+/// The apex is kept at least this far above the launch point, or the body would never get there
+const MIN_APEX_HEIGHT: f32 = 1.;
+
+/// Velocity that makes a body launched at `from` peak at `apex`.
+/// Gravity has to point down, apexes below `from` are raised to just above it.
+pub fn launch_to_apex(from: Vec2, apex: Vec2, gravity: Vec2) -> Vec2 {
+    let height = (apex.y - from.y).max(MIN_APEX_HEIGHT);
+    // the body rises by -g * dt² * n(n-1)/2 in n steps, solve for the time to the apex
+    let time = (SUB_DT + (SUB_DT * SUB_DT - 8. * height / gravity.y).sqrt()) / 2.;
+    Vec2::new((apex.x - from.x) / time, -gravity.y * time)
+}
+
+/// Velocity that makes a body launched at `from` pass through `target` after `time` seconds
+pub fn launch_to_target(from: Vec2, target: Vec2, gravity: Vec2, time: f32) -> Vec2 {
+    (target - from) / time - 0.5 * gravity * (time + SUB_DT)
+}
+
+/// Positions of a body launched at `from`, one per time step
+pub fn trajectory(from: Vec2, vel: Vec2, gravity: Vec2) -> impl Iterator<Item = Vec2> {
+    trajectory_with(from, vel, move |_| gravity)
+}
+
+/// Like `trajectory`, but the acceleration can change along the way, e.g. in force zones
+pub fn trajectory_with(
+    from: Vec2,
+    vel: Vec2,
+    accel: impl Fn(Vec2) -> Vec2,
+) -> impl Iterator<Item = Vec2> {
+    (1..).scan((from, vel), move |(pos, vel), _| {
+        // same order as in integrate, the acceleration is taken where the step starts
+        *vel += SUB_DT * accel(*pos);
+        *pos += SUB_DT * *vel;
+        Some(*pos)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn gravity() -> Vec2 {
+        Vec2::new(0., -1000.)
+    }
+
+    #[test]
+    fn apex_straight_up() {
+        let vel = launch_to_apex(Vec2::ZERO, Vec2::new(0., 100.), gravity());
+        assert!(vel.x.abs() < 0.001);
+        assert!(vel.y > 0.);
+    }
+
+    #[test]
+    fn apex_below_start_still_goes_up() {
+        let vel = launch_to_apex(Vec2::ZERO, Vec2::new(10., -50.), gravity());
+        assert!(vel.y > 0.);
+    }
+
+    proptest! {
+        #[test]
+        fn reaches_apex(x in -300f32..300., y in 10f32..300.) {
+            let apex = Vec2::new(x, y);
+            let vel = launch_to_apex(Vec2::ZERO, apex, gravity());
+            let highest = trajectory(Vec2::ZERO, vel, gravity())
+                .take(600)
+                .reduce(|highest, p| if p.y > highest.y { p } else { highest })
+                .unwrap();
+            // the apex is usually between two steps
+            prop_assert!((highest - apex).length() < vel.length() * SUB_DT);
+        }
+
+        #[test]
+        fn hits_target(x in -300f32..300., y in -300f32..300., steps in 10usize..120) {
+            let target = Vec2::new(x, y);
+            let vel = launch_to_target(Vec2::ZERO, target, gravity(), steps as f32 * SUB_DT);
+            let pos = trajectory(Vec2::ZERO, vel, gravity()).nth(steps - 1).unwrap();
+            prop_assert!((pos - target).length() < 0.01);
+        }
+    }
+}
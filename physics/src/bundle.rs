@@ -11,6 +11,7 @@ pub struct ParticleBundle {
     pub vel: Vel,
     pub pre_solve_vel: PreSolveVel,
     pub restitution: Restitution,
+    pub friction: Friction,
     pub aabb: Aabb,
 }
 
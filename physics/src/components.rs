@@ -0,0 +1,171 @@
+use bevy::prelude::*;
+use derive_more::From;
+
+// todo: register all of these as rollback components
+
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct Aabb {
+    pub(crate) min: Vec2,
+    pub(crate) max: Vec2,
+}
+
+impl Aabb {
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.max.x >= other.min.x
+            && self.max.y >= other.min.y
+            && self.min.x <= other.max.x
+            && self.min.y <= other.max.y
+    }
+}
+
+#[derive(Component, Reflect, Debug, Clone, Copy, From)]
+#[reflect(Component)]
+pub struct CircleCollider {
+    pub radius: f32,
+}
+
+impl Default for CircleCollider {
+    fn default() -> Self {
+        Self { radius: 0.5 }
+    }
+}
+
+#[derive(Component, Reflect, Debug, Clone, Copy, From)]
+#[reflect(Component)]
+pub struct BoxCollider {
+    pub size: Vec2,
+}
+
+impl BoxCollider {
+    // pub fn inertia_inv_from_mass_inv(&self, mass_inv: f32) -> f32 {
+    //     12. * mass_inv / self.size.length_squared()
+    // }
+}
+
+impl Default for BoxCollider {
+    fn default() -> Self {
+        Self { size: Vec2::ONE }
+    }
+}
+
+#[derive(Component, Reflect, Debug, Default, Clone, Copy, From)]
+#[reflect(Component)]
+pub struct Pos(pub Vec2);
+
+#[derive(Component, Reflect, Debug, Default, Clone, Copy, From)]
+#[reflect(Component)]
+pub struct PrevPos(pub Vec2);
+
+#[derive(Component, Reflect, Clone, Copy, Debug, Default)]
+#[reflect(Component)]
+pub struct Vel(pub Vec2);
+
+#[derive(Component, Reflect, Debug, Default)]
+#[reflect(Component)]
+pub struct PreSolveVel(pub Vec2);
+
+#[derive(Component, Reflect, Debug, Clone, Copy, From)]
+#[reflect(Component)]
+pub struct Mass(pub f32);
+
+impl Default for Mass {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Restitution(pub f32);
+
+impl Default for Restitution {
+    fn default() -> Self {
+        Self(0.) // no bounce, could just derive...
+    }
+}
+
+/// Slows down bodies sliding along each other. Only particles have it by default,
+/// the friction of a contact is the average of both bodies, missing counts as 0.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Friction(pub f32);
+
+impl Default for Friction {
+    fn default() -> Self {
+        Self(0.5)
+    }
+}
+
+/// Makes a static body a one-way platform: dynamic bodies only collide with its top,
+/// and only if they were above it in the previous step. Anything else passes through.
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct OneWay;
+
+/// Pushes every dynamic body whose center is inside the box, like a fan blowing.
+/// The force is applied on top of gravity, so lighter bodies are pushed harder.
+/// A force zone has no collider, bodies pass right through it.
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct ForceZone {
+    pub size: Vec2,
+    pub force: Vec2,
+}
+
+impl ForceZone {
+    pub fn contains(&self, zone_pos: Vec2, pos: Vec2) -> bool {
+        let dist = (pos - zone_pos).abs();
+        dist.x < self.size.x / 2. && dist.y < self.size.y / 2.
+    }
+}
+
+/// Marks a particle as part of a soft body.
+/// Particles are addressed by their index within the body instead of by entity,
+/// so references stay valid when a rollback respawns them.
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct SoftBodyParticle {
+    pub body: u32,
+    pub index: u32,
+}
+
+/// XPBD distance constraint between two particles `a` and `b` of the soft body `body`.
+/// A compliance of 0 makes the constraint rigid, bigger values make it softer.
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct DistanceConstraint {
+    pub body: u32,
+    pub a: u32,
+    pub b: u32,
+    pub rest_length: f32,
+    pub compliance: f32,
+}
+
+/// Dynamic bodies only collide with each other if each one is a member of a layer the other one filters for.
+/// Bodies without this component collide with everything.
+#[derive(Component, Reflect, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct CollisionLayers {
+    pub memberships: u32,
+    pub filters: u32,
+}
+
+impl CollisionLayers {
+    pub fn new(memberships: u32, filters: u32) -> Self {
+        Self {
+            memberships,
+            filters,
+        }
+    }
+
+    pub fn interacts_with(&self, other: &Self) -> bool {
+        self.memberships & other.filters != 0 && other.memberships & self.filters != 0
+    }
+}
+
+impl Default for CollisionLayers {
+    fn default() -> Self {
+        Self::new(u32::MAX, u32::MAX)
+    }
+}
@@ -0,0 +1,287 @@
+use bevy::prelude::*;
+
+pub struct Contact {
+    pub penetration: f32,
+    pub normal: Vec2,
+}
+
+pub fn ball_ball(pos_a: Vec2, radius_a: f32, pos_b: Vec2, radius_b: f32) -> Option<Contact> {
+    let ab = pos_b - pos_a;
+    let combined_radius = radius_a + radius_b;
+    let ab_sqr_len = ab.length_squared();
+    if ab_sqr_len < combined_radius * combined_radius {
+        let ab_length = ab_sqr_len.sqrt();
+        let penetration = combined_radius - ab_length;
+        let normal = ab / ab_length;
+        Some(Contact {
+            normal,
+            penetration,
+        })
+    } else {
+        None
+    }
+}
+
+pub fn ball_box(pos_a: Vec2, radius_a: f32, pos_b: Vec2, size_b: Vec2) -> Option<Contact> {
+    let box_to_circle = pos_a - pos_b;
+    let box_to_circle_abs = box_to_circle.abs();
+    let half_extents = size_b / 2.;
+    let corner_to_center = box_to_circle_abs - half_extents;
+    let r = radius_a;
+    if corner_to_center.x > r || corner_to_center.y > r {
+        return None;
+    }
+
+    let s = box_to_circle.signum();
+
+    let (n, penetration) = if corner_to_center.x > 0. && corner_to_center.y > 0. {
+        // Corner case
+        let corner_to_center_sqr = corner_to_center.length_squared();
+        if corner_to_center_sqr > r * r {
+            return None;
+        }
+        let corner_dist = corner_to_center_sqr.sqrt();
+        let penetration = r - corner_dist;
+        let n = corner_to_center / corner_dist * -s;
+        (n, penetration)
+    } else if corner_to_center.x > corner_to_center.y {
+        // Closer to vertical edge
+        (Vec2::X * -s.x, -corner_to_center.x + r)
+    } else {
+        (Vec2::Y * -s.y, -corner_to_center.y + r)
+    };
+
+    Some(Contact {
+        normal: n,
+        penetration,
+    })
+}
+
+pub fn box_box(pos_a: Vec2, size_a: Vec2, pos_b: Vec2, size_b: Vec2) -> Option<Contact> {
+    let half_a = size_a / 2.;
+    let half_b = size_b / 2.;
+    let ab = pos_b - pos_a;
+    let overlap = (half_a + half_b) - ab.abs(); // exploit symmetry
+    if overlap.x < 0. || overlap.y < 0. {
+        None
+    } else if overlap.x < overlap.y {
+        // closer to vertical edge
+        Some(Contact {
+            penetration: overlap.x,
+            normal: Vec2::X * ab.x.signum(),
+        })
+    } else {
+        // closer to horizontal edge
+        Some(Contact {
+            penetration: overlap.y,
+            normal: Vec2::Y * ab.y.signum(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn box_box_clear() {
+        assert!(box_box(Vec2::ZERO, Vec2::ONE, Vec2::new(1.1, 0.), Vec2::ONE).is_none());
+        assert!(box_box(Vec2::ZERO, Vec2::ONE, Vec2::new(-1.1, 0.), Vec2::ONE).is_none());
+        assert!(box_box(Vec2::ZERO, Vec2::ONE, Vec2::new(0., 1.1), Vec2::ONE).is_none());
+        assert!(box_box(Vec2::ZERO, Vec2::ONE, Vec2::new(0., -1.1), Vec2::ONE).is_none());
+    }
+
+    #[test]
+    fn box_box_intersection() {
+        assert!(box_box(Vec2::ZERO, Vec2::ONE, Vec2::ZERO, Vec2::ONE).is_some());
+        assert!(box_box(Vec2::ZERO, Vec2::ONE, Vec2::new(0.9, 0.9), Vec2::ONE).is_some());
+        assert!(box_box(Vec2::ZERO, Vec2::ONE, Vec2::new(-0.9, -0.9), Vec2::ONE).is_some());
+    }
+
+    #[test]
+    fn box_box_contact_horizontal() {
+        let Contact {
+            normal,
+            penetration,
+        } = box_box(Vec2::ZERO, Vec2::ONE, Vec2::new(0.9, 0.), Vec2::ONE).unwrap();
+
+        assert!(normal.x > 0.999);
+        assert!(normal.y < 0.001);
+        assert!((penetration - 0.1).abs() < 0.001);
+    }
+
+    #[test]
+    fn box_box_contact_vertical() {
+        let Contact {
+            normal,
+            penetration,
+        } = box_box(Vec2::ZERO, Vec2::ONE, Vec2::new(0., 0.9), Vec2::ONE).unwrap();
+
+        assert!(normal.y > 0.999);
+        assert!(normal.x < 0.001);
+        assert!((penetration - 0.1).abs() < 0.001);
+    }
+
+    #[test]
+    fn ball_ball_clear() {
+        assert!(ball_ball(Vec2::ZERO, 0.5, Vec2::new(1.1, 0.), 0.5).is_none());
+        assert!(ball_ball(Vec2::ZERO, 0.5, Vec2::new(0.8, 0.8), 0.5).is_none());
+    }
+
+    #[test]
+    fn ball_ball_contact() {
+        let Contact {
+            normal,
+            penetration,
+        } = ball_ball(Vec2::ZERO, 0.5, Vec2::new(0., 0.9), 0.5).unwrap();
+
+        assert!(normal.y > 0.999);
+        assert!(normal.x.abs() < 0.001);
+        assert!((penetration - 0.1).abs() < 0.001);
+    }
+
+    #[test]
+    fn ball_box_clear() {
+        assert!(ball_box(Vec2::new(1.1, 0.), 0.5, Vec2::ZERO, Vec2::ONE).is_none());
+        assert!(ball_box(Vec2::new(0., -1.1), 0.5, Vec2::ZERO, Vec2::ONE).is_none());
+        // inside the corner region of the aabb, but not touching the corner
+        assert!(ball_box(Vec2::new(0.9, 0.9), 0.5, Vec2::ZERO, Vec2::ONE).is_none());
+    }
+
+    #[test]
+    fn ball_box_contact_edge() {
+        let Contact {
+            normal,
+            penetration,
+        } = ball_box(Vec2::new(0.9, 0.), 0.5, Vec2::ZERO, Vec2::ONE).unwrap();
+
+        // the normal points from the ball to the box
+        assert!(normal.x < -0.999);
+        assert!(normal.y.abs() < 0.001);
+        assert!((penetration - 0.1).abs() < 0.001);
+
+        let Contact {
+            normal,
+            penetration,
+        } = ball_box(Vec2::new(0., 0.9), 0.5, Vec2::ZERO, Vec2::ONE).unwrap();
+
+        assert!(normal.y < -0.999);
+        assert!(normal.x.abs() < 0.001);
+        assert!((penetration - 0.1).abs() < 0.001);
+    }
+
+    #[test]
+    fn ball_box_contact_corner() {
+        let Contact {
+            normal,
+            penetration,
+        } = ball_box(Vec2::new(0.8, 0.8), 0.5, Vec2::ZERO, Vec2::ONE).unwrap();
+
+        let expected = -Vec2::ONE.normalize();
+        assert!((normal - expected).length() < 0.001);
+        assert!((penetration - (0.5 - Vec2::splat(0.3).length())).abs() < 0.001);
+    }
+
+    fn vec2(range: f32) -> impl Strategy<Value = Vec2> {
+        (-range..range, -range..range).prop_map(|(x, y)| Vec2::new(x, y))
+    }
+
+    fn size() -> impl Strategy<Value = Vec2> {
+        (0.1f32..5., 0.1f32..5.).prop_map(|(x, y)| Vec2::new(x, y))
+    }
+
+    fn is_unit(v: Vec2) -> bool {
+        (v.length() - 1.).abs() < 1e-4
+    }
+
+    proptest! {
+        #[test]
+        fn ball_ball_symmetric(pos_a in vec2(5.), r_a in 0.1f32..3., pos_b in vec2(5.), r_b in 0.1f32..3.) {
+            prop_assume!(pos_a != pos_b);
+            let ab = ball_ball(pos_a, r_a, pos_b, r_b);
+            let ba = ball_ball(pos_b, r_b, pos_a, r_a);
+            prop_assert_eq!(ab.is_some(), ba.is_some());
+            if let (Some(ab), Some(ba)) = (ab, ba) {
+                prop_assert_eq!(ab.normal, -ba.normal);
+                prop_assert_eq!(ab.penetration, ba.penetration);
+            }
+        }
+
+        #[test]
+        fn ball_ball_normal_points_from_a_to_b(pos_a in vec2(5.), r_a in 0.1f32..3., pos_b in vec2(5.), r_b in 0.1f32..3.) {
+            prop_assume!(pos_a.distance(pos_b) > 1e-3);
+            if let Some(Contact { normal, penetration }) = ball_ball(pos_a, r_a, pos_b, r_b) {
+                prop_assert!(is_unit(normal));
+                prop_assert!(normal.dot(pos_b - pos_a) > 0.);
+                prop_assert!(penetration > 0. && penetration <= r_a + r_b);
+            }
+        }
+
+        #[test]
+        fn ball_ball_resolves_penetration(pos_a in vec2(5.), r_a in 0.1f32..3., pos_b in vec2(5.), r_b in 0.1f32..3.) {
+            prop_assume!(pos_a.distance(pos_b) > 1e-3);
+            if let Some(Contact { normal, penetration }) = ball_ball(pos_a, r_a, pos_b, r_b) {
+                let resolved = pos_a - normal * penetration;
+                if let Some(contact) = ball_ball(resolved, r_a, pos_b, r_b) {
+                    prop_assert!(contact.penetration < 1e-3);
+                }
+            }
+        }
+
+        #[test]
+        fn ball_box_normal_points_from_ball_to_box(pos_a in vec2(5.), r_a in 0.1f32..3., pos_b in vec2(5.), size_b in size()) {
+            if let Some(Contact { normal, penetration }) = ball_box(pos_a, r_a, pos_b, size_b) {
+                prop_assert!(is_unit(normal));
+                // the normal is either axis aligned or, in the corner case, diagonal
+                prop_assert!(normal.dot(pos_b - pos_a) >= 0.);
+                prop_assert!(penetration >= 0.);
+            }
+        }
+
+        #[test]
+        fn ball_box_resolves_penetration(pos_a in vec2(5.), r_a in 0.1f32..3., pos_b in vec2(5.), size_b in size()) {
+            if let Some(Contact { normal, penetration }) = ball_box(pos_a, r_a, pos_b, size_b) {
+                let resolved = pos_a - normal * penetration;
+                if let Some(contact) = ball_box(resolved, r_a, pos_b, size_b) {
+                    prop_assert!(contact.penetration < 1e-3);
+                }
+            }
+        }
+
+        #[test]
+        fn box_box_symmetric(pos_a in vec2(5.), size_a in size(), pos_b in vec2(5.), size_b in size()) {
+            // signum(0.) is positive, so coincident centers have no well defined direction
+            prop_assume!(pos_a.x != pos_b.x && pos_a.y != pos_b.y);
+            let ab = box_box(pos_a, size_a, pos_b, size_b);
+            let ba = box_box(pos_b, size_b, pos_a, size_a);
+            prop_assert_eq!(ab.is_some(), ba.is_some());
+            if let (Some(ab), Some(ba)) = (ab, ba) {
+                prop_assert_eq!(ab.normal, -ba.normal);
+                prop_assert_eq!(ab.penetration, ba.penetration);
+            }
+        }
+
+        #[test]
+        fn box_box_normal_points_from_a_to_b(pos_a in vec2(5.), size_a in size(), pos_b in vec2(5.), size_b in size()) {
+            if let Some(Contact { normal, penetration }) = box_box(pos_a, size_a, pos_b, size_b) {
+                prop_assert!(normal == Vec2::X || normal == -Vec2::X || normal == Vec2::Y || normal == -Vec2::Y);
+                prop_assert!(normal.dot(pos_b - pos_a) >= 0.);
+                prop_assert!(penetration >= 0.);
+                // the penetration is the smallest overlap
+                let overlap = (size_a + size_b) / 2. - (pos_b - pos_a).abs();
+                prop_assert!(penetration <= overlap.x && penetration <= overlap.y);
+            }
+        }
+
+        #[test]
+        fn box_box_resolves_penetration(pos_a in vec2(5.), size_a in size(), pos_b in vec2(5.), size_b in size()) {
+            if let Some(Contact { normal, penetration }) = box_box(pos_a, size_a, pos_b, size_b) {
+                let resolved = pos_a - normal * penetration;
+                if let Some(contact) = box_box(resolved, size_a, pos_b, size_b) {
+                    prop_assert!(contact.penetration < 1e-3);
+                }
+            }
+        }
+    }
+}
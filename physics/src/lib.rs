@@ -0,0 +1,413 @@
+//! simplified version of bevy_xpbd
+//!
+//! The simulation runs with a fixed time step and solves everything sequentially,
+//! so it stays deterministic and can be used inside a rollback schedule.
+//!
+//! Add the [`PhysicsPlugin`] to get the physics resources and a stage in the default schedule.
+//! If the physics should run in a custom schedule instead (e.g. a GGRS rollback schedule),
+//! use [`PhysicsPlugin::without_stage`] and add the stage from [`create_physics_stage`] yourself:
+//!
+//! ```ignore
+//! let schedule = Schedule::default().with_stage(PhysicsUpdateStage, create_physics_stage());
+//! app.add_plugin(PhysicsPlugin::without_stage());
+//! ```
+
+use bevy::prelude::*;
+use bevy_system_graph::SystemGraph;
+use systems::*;
+
+use resources::*;
+
+pub mod ballistics;
+mod bundle;
+pub mod components;
+mod contact;
+mod resources;
+mod systems;
+mod utils;
+
+pub struct PhysicsPlugin {
+    add_stage: bool,
+}
+
+impl Default for PhysicsPlugin {
+    fn default() -> Self {
+        Self { add_stage: true }
+    }
+}
+
+impl PhysicsPlugin {
+    /// Only adds the physics resources. The stage from [`create_physics_stage`] has to be added
+    /// to a schedule manually, e.g. when running the physics in a rollback schedule.
+    pub fn without_stage() -> Self {
+        Self { add_stage: false }
+    }
+}
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Gravity>()
+            // .init_resource::<LoopState>() // todo: for substepping
+            // These resources are cleared at the start of every physics frame, so they should be rollback safe
+            // i.e. they do not need to be added as rollback resources.
+            .init_resource::<CollisionPairs>()
+            .init_resource::<Contacts>()
+            .init_resource::<StaticContacts>();
+
+        if self.add_stage {
+            app.add_stage_after(
+                CoreStage::Update,
+                PhysicsUpdateStage,
+                create_physics_stage(),
+            );
+        }
+    }
+}
+
+/// re-exports of things needed to to use the physics crate
+pub mod prelude {
+    pub use super::{
+        bundle::*,
+        components::{BoxCollider, Pos, Vel},
+        create_physics_stage,
+        resources::{Contacts, Gravity, StaticContacts},
+        PhysicsPlugin, PhysicsUpdateStage,
+    };
+}
+
+pub const DELTA_TIME: f32 = 1. / 60.;
+pub const NUM_SUBSTEPS: u32 = 1; // todo
+pub const SUB_DT: f32 = DELTA_TIME / NUM_SUBSTEPS as f32;
+/// Safety margin bigger than DELTA_TIME added to AABBs to account for sudden accelerations
+const COLLISION_PAIR_VEL_MARGIN_FACTOR: f32 = 2. * DELTA_TIME;
+/// How far a body may have sunk into a one-way platform in the previous step and still land on it
+const ONE_WAY_MARGIN: f32 = 0.01;
+
+#[derive(SystemLabel, Debug, Hash, PartialEq, Eq, Clone)]
+enum Step {
+    ComputeAabbs,
+    CollectCollisionPairs,
+    Integrate,
+    SolvePositions,
+    UpdateVelocities,
+    SolveVelocities,
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
+pub struct PhysicsUpdateStage;
+
+pub fn create_physics_stage() -> SystemStage {
+    let solve_pos_systems: SystemSet = {
+        let graph = SystemGraph::new();
+        graph
+            .root(solve_pos_distance_constraints)
+            .then(solve_pos_ball_ball)
+            // Run solvers sequentially to make sure rollback is deterministic
+            // box_box and ball_ball could probably run in parallel,
+            // but just keep it simple for now, wasm isn't parallel anyway
+            .then(solve_pos_box_box)
+            .then(solve_pos_ball_box)
+            .then(solve_pos_static_ball_ball)
+            .then(solve_pos_static_box_ball)
+            .then(solve_pos_static_box_box);
+        graph.into()
+    };
+
+    let solve_vel_systems: SystemSet = {
+        let graph = SystemGraph::new();
+        graph.root(solve_vel).then(solve_vel_statics);
+        graph.into()
+    };
+
+    SystemStage::parallel()
+        // todo: needed if we implement substepping
+        // .with_run_criteria(run_criteria)
+        .with_system_set(
+            SystemSet::new()
+                .label(Step::ComputeAabbs)
+                .before(Step::CollectCollisionPairs)
+                .with_system(update_aabb_box)
+                .with_system(update_aabb_ball),
+        )
+        .with_system(
+            collect_collision_pairs
+                // .with_run_criteria(first_substep) // todo: for substepping
+                .label(Step::CollectCollisionPairs)
+                .before(Step::Integrate),
+        )
+        .with_system_set(
+            SystemSet::new()
+                .label(Step::Integrate)
+                .with_system(integrate),
+            // .with_system(integrate_rot), // todo: for rotation
+        )
+        .with_system(clear_contacts.before(Step::SolvePositions))
+        .with_system_set(
+            solve_pos_systems
+                .label(Step::SolvePositions)
+                .after(Step::Integrate),
+        )
+        .with_system_set(
+            SystemSet::new()
+                .label(Step::UpdateVelocities)
+                .after(Step::SolvePositions)
+                .with_system(update_vel),
+            // .with_system(update_ang_vel), todo: for rotation
+        )
+        .with_system_set(
+            solve_vel_systems
+                .label(Step::SolveVelocities)
+                .after(Step::UpdateVelocities),
+        )
+        .with_system(
+            sync_transforms
+                // .with_run_criteria(last_substep) // todo: substepping
+                .after(Step::SolveVelocities),
+        )
+}
+
+// Stuff for substepping, if we enable that:
+// We'd want to enable substepping if we have issues with tunneling, or collisions feel too "spongy"
+// (it's pretty cheap, the expensive parts of the simulation only run once per frame)
+
+// #[derive(Debug, Default)]
+// struct LoopState {
+//     has_added_time: bool,
+//     accumulator: f32,
+//     substepping: bool,
+//     current_substep: u32,
+// }
+
+// fn run_criteria(time: Res<Time>, mut state: ResMut<LoopState>) -> ShouldRun {
+//     if !state.has_added_time {
+//         state.has_added_time = true;
+//         state.accumulator += time.delta_seconds();
+//     }
+
+//     if state.substepping {
+//         state.current_substep += 1;
+
+//         if state.current_substep < NUM_SUBSTEPS {
+//             return ShouldRun::YesAndCheckAgain;
+//         } else {
+//             // We finished a whole step
+//             state.accumulator -= DELTA_TIME;
+//             state.current_substep = 0;
+//             state.substepping = false;
+//         }
+//     }
+
+//     if state.accumulator >= DELTA_TIME {
+//         state.substepping = true;
+//         state.current_substep = 0;
+//         ShouldRun::YesAndCheckAgain
+//     } else {
+//         state.has_added_time = false;
+//         ShouldRun::No
+//     }
+// }
+
+// fn first_substep(state: Res<LoopState>) -> ShouldRun {
+//     if state.current_substep == 0 {
+//         ShouldRun::Yes
+//     } else {
+//         ShouldRun::No
+//     }
+// }
+
+// fn last_substep(state: Res<LoopState>) -> ShouldRun {
+//     if state.current_substep == NUM_SUBSTEPS - 1 {
+//         ShouldRun::Yes
+//     } else {
+//         ShouldRun::No
+//     }
+// }
+
+#[cfg(test)]
+mod tests {
+    use super::{ballistics, components::*, prelude::*};
+    use bevy::prelude::*;
+
+    /// Simulates a small pile of boxes and balls falling onto the ground,
+    /// returns the raw bits of all final dynamic body positions.
+    fn simulate(frames: usize) -> Vec<(u32, u32, u32)> {
+        let mut app = App::new();
+        app.add_plugin(PhysicsPlugin::default());
+
+        app.world.spawn().insert_bundle(StaticBoxBundle {
+            pos: Pos(Vec2::new(0., -5.)),
+            collider: BoxCollider {
+                size: Vec2::new(20., 1.),
+            },
+            ..Default::default()
+        });
+        for i in 0..5 {
+            let x = i as f32 * 0.7 - 1.4;
+            app.world.spawn().insert_bundle(DynamicBoxBundle {
+                pos: Pos(Vec2::new(x, i as f32)),
+                prev_pos: PrevPos(Vec2::new(x, i as f32)),
+                vel: Vel(Vec2::new(-x, 0.)),
+                restitution: Restitution(0.3),
+                ..Default::default()
+            });
+            app.world.spawn().insert_bundle(ParticleBundle {
+                pos: Pos(Vec2::new(-x, i as f32 + 0.5)),
+                prev_pos: PrevPos(Vec2::new(-x, i as f32 + 0.5)),
+                collider: CircleCollider { radius: 0.3 },
+                mass: Mass(0.5),
+                ..Default::default()
+            });
+        }
+
+        for _ in 0..frames {
+            app.update();
+        }
+
+        let mut query = app.world.query_filtered::<(Entity, &Pos), With<Mass>>();
+        let mut positions: Vec<_> = query
+            .iter(&app.world)
+            .map(|(e, pos)| (e.id(), pos.0.x.to_bits(), pos.0.y.to_bits()))
+            .collect();
+        positions.sort_unstable();
+        positions
+    }
+
+    #[test]
+    fn deterministic() {
+        let first = simulate(120);
+        let second = simulate(120);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn one_way_platforms_only_block_from_above() {
+        let mut app = App::new();
+        app.add_plugin(PhysicsPlugin::default());
+
+        // platform top is at 0.25
+        app.world
+            .spawn()
+            .insert_bundle(StaticBoxBundle {
+                pos: Pos(Vec2::ZERO),
+                collider: BoxCollider {
+                    size: Vec2::new(6., 0.5),
+                },
+                ..Default::default()
+            })
+            .insert(OneWay);
+        app.world.spawn().insert_bundle(StaticBoxBundle {
+            pos: Pos(Vec2::new(0., -5.)),
+            collider: BoxCollider {
+                size: Vec2::new(20., 1.),
+            },
+            ..Default::default()
+        });
+        // one box falls onto the platform, the other one jumps through it from below
+        let falling = app
+            .world
+            .spawn()
+            .insert_bundle(DynamicBoxBundle {
+                pos: Pos(Vec2::new(-1., 2.)),
+                prev_pos: PrevPos(Vec2::new(-1., 2.)),
+                ..Default::default()
+            })
+            .id();
+        let jumping = app
+            .world
+            .spawn()
+            .insert_bundle(DynamicBoxBundle {
+                pos: Pos(Vec2::new(1., -2.)),
+                prev_pos: PrevPos(Vec2::new(1., -2.)),
+                vel: Vel(Vec2::new(0., 10.)),
+                ..Default::default()
+            })
+            .id();
+
+        for _ in 0..300 {
+            app.update();
+        }
+
+        for entity in [falling, jumping] {
+            let y = app.world.get::<Pos>(entity).unwrap().0.y;
+            assert!((y - 0.75).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn force_zones_only_push_bodies_inside() {
+        let mut app = App::new();
+        app.add_plugin(PhysicsPlugin::default());
+
+        app.world.spawn().insert(Pos(Vec2::ZERO)).insert(ForceZone {
+            size: Vec2::new(4., 100.),
+            force: Vec2::new(10., 0.),
+        });
+        let inside = app
+            .world
+            .spawn()
+            .insert_bundle(ParticleBundle::default())
+            .id();
+        let outside = app
+            .world
+            .spawn()
+            .insert_bundle(ParticleBundle {
+                pos: Pos(Vec2::new(10., 0.)),
+                prev_pos: PrevPos(Vec2::new(10., 0.)),
+                ..Default::default()
+            })
+            .id();
+
+        for _ in 0..10 {
+            app.update();
+        }
+
+        assert!(app.world.get::<Vel>(inside).unwrap().0.x > 0.);
+        assert_eq!(app.world.get::<Vel>(outside).unwrap().0.x, 0.);
+    }
+
+    #[test]
+    fn trajectories_follow_force_zones() {
+        let mut app = App::new();
+        app.add_plugin(PhysicsPlugin::default());
+
+        let zone_pos = Vec2::new(5., 0.);
+        let zone = ForceZone {
+            size: Vec2::new(6., 100.),
+            force: Vec2::new(20., 5.),
+        };
+        app.world.spawn().insert(Pos(zone_pos)).insert(zone);
+        let vel = Vec2::new(30., 10.);
+        let body = app
+            .world
+            .spawn()
+            .insert_bundle(ParticleBundle {
+                vel: Vel(vel),
+                ..Default::default()
+            })
+            .id();
+        let mass = app.world.get::<Mass>(body).unwrap().0;
+        let gravity = app.world.get_resource::<Gravity>().unwrap().0;
+
+        let expected = ballistics::trajectory_with(Vec2::ZERO, vel, |pos| {
+            if zone.contains(zone_pos, pos) {
+                gravity + zone.force / mass
+            } else {
+                gravity
+            }
+        });
+        for expected in expected.take(30) {
+            app.update();
+            let pos = app.world.get::<Pos>(body).unwrap().0;
+            assert!((pos - expected).length() < 0.001);
+        }
+    }
+
+    #[test]
+    fn bodies_come_to_rest_on_the_ground() {
+        let positions = simulate(300);
+        // top of the ground is at -4.5
+        for (_, _, y) in positions {
+            assert!(f32::from_bits(y) > -4.5);
+        }
+    }
+}
@@ -1,19 +1,16 @@
 use bevy::prelude::*;
 
-use crate::round::{JUMP_HEIGHT, JUMP_TIME_TO_PEAK};
-
-use super::PIXELS_PER_METER;
-
+/// Global gravity acceleration, in the same units as [`crate::components::Pos`] per second squared.
+///
+/// The default is real-world gravity in meters. Insert your own value before adding the
+/// [`crate::PhysicsPlugin`] if your world uses different units, e.g. pixels.
 #[derive(Debug)]
 pub struct Gravity(pub Vec2);
 
 impl Default for Gravity {
     fn default() -> Self {
-        // For real-world gravity,
         // we should probably tweak this, though. Maybe even have per object gravity?
-        // Self(Vec2::new(0., -9.81 * PIXELS_PER_METER))
-        let grav = (-2. * JUMP_HEIGHT) / JUMP_TIME_TO_PEAK; // derived as suggested in: https://www.youtube.com/watch?v=hG9SzQxaCm8
-        Self(Vec2::new(0., grav * PIXELS_PER_METER))
+        Self(Vec2::new(0., -9.81))
     }
 }
 
@@ -0,0 +1,651 @@
+use crate::contact;
+use crate::contact::Contact;
+use crate::utils::QueryExt;
+use crate::SUB_DT;
+
+use super::components::*;
+use super::resources::*;
+use super::{COLLISION_PAIR_VEL_MARGIN_FACTOR, ONE_WAY_MARGIN};
+use bevy::{prelude::*, utils::HashMap};
+
+pub fn update_aabb_ball(mut query: Query<(&mut Aabb, &Pos, &Vel, &CircleCollider)>) {
+    for (mut aabb, pos, vel, circle) in query.iter_mut() {
+        let margin = COLLISION_PAIR_VEL_MARGIN_FACTOR * vel.0.length();
+        let half_extents = Vec2::splat(circle.radius + margin);
+        aabb.min = pos.0 - half_extents;
+        aabb.max = pos.0 + half_extents;
+    }
+}
+
+pub fn update_aabb_box(mut query: Query<(&mut Aabb, &Pos, &Vel, &BoxCollider)>) {
+    for (mut aabb, pos, vel, r#box) in query.iter_mut() {
+        let margin = COLLISION_PAIR_VEL_MARGIN_FACTOR * vel.0.length();
+        let half_extents = r#box.size / 2. + Vec2::splat(margin);
+        aabb.min = pos.0 - half_extents;
+        aabb.max = pos.0 + half_extents;
+    }
+}
+
+pub fn collect_collision_pairs(
+    query: Query<(Entity, &Aabb, Option<&CollisionLayers>)>,
+    mut collision_pairs: ResMut<CollisionPairs>,
+) {
+    debug!("collect_collision_pairs");
+    collision_pairs.0.clear();
+
+    let mut iter = query.iter_combinations();
+    while let Some([(entity_a, aabb_a, layers_a), (entity_b, aabb_b, layers_b)]) = iter.fetch_next()
+    {
+        let layers_a = layers_a.copied().unwrap_or_default();
+        let layers_b = layers_b.copied().unwrap_or_default();
+        if layers_a.interacts_with(&layers_b) && aabb_a.intersects(aabb_b) {
+            collision_pairs.0.push((entity_a, entity_b));
+        }
+    }
+}
+
+pub fn integrate(
+    mut query: Query<(&mut Pos, &mut PrevPos, &mut Vel, &mut PreSolveVel, &Mass)>,
+    zones: Query<(&Pos, &ForceZone), Without<Mass>>,
+    gravity: Res<Gravity>,
+) {
+    debug!("  integrate");
+    for (mut pos, mut prev_pos, mut vel, mut pre_solve_vel, mass) in query.iter_mut() {
+        prev_pos.0 = pos.0;
+
+        let gravitation_force = mass.0 * gravity.0;
+        let zone_force = zones
+            .iter()
+            .filter(|(zone_pos, zone)| zone.contains(zone_pos.0, pos.0))
+            .fold(Vec2::ZERO, |sum, (_, zone)| sum + zone.force);
+        let external_forces = gravitation_force + zone_force;
+        vel.0 += SUB_DT * external_forces / mass.0;
+        pos.0 += SUB_DT * vel.0;
+        pre_solve_vel.0 = vel.0;
+    }
+}
+
+pub fn clear_contacts(mut contacts: ResMut<Contacts>, mut static_contacts: ResMut<StaticContacts>) {
+    debug!("  clear_contacts");
+    contacts.0.clear();
+    static_contacts.0.clear();
+}
+
+pub fn solve_pos_ball_ball(
+    mut query: Query<(&mut Pos, &CircleCollider, &Mass)>,
+    mut contacts: ResMut<Contacts>,
+    collision_pairs: Res<CollisionPairs>,
+) {
+    debug!("  solve_pos");
+    for (entity_a, entity_b) in collision_pairs.0.iter().cloned() {
+        if let Ok(((mut pos_a, circle_a, mass_a), (mut pos_b, circle_b, mass_b))) =
+            query.get_pair_mut(entity_a, entity_b)
+        {
+            if let Some(Contact {
+                normal,
+                penetration,
+            }) = contact::ball_ball(pos_a.0, circle_a.radius, pos_b.0, circle_b.radius)
+            {
+                constrain_body_positions(
+                    &mut pos_a,
+                    &mut pos_b,
+                    mass_a,
+                    mass_b,
+                    normal,
+                    penetration,
+                );
+                contacts.0.push((entity_a, entity_b, normal));
+            }
+        }
+    }
+}
+
+pub fn solve_pos_box_box(
+    mut query: Query<(&mut Pos, &BoxCollider, &Mass)>,
+    mut contacts: ResMut<Contacts>,
+    collision_pairs: Res<CollisionPairs>,
+) {
+    for (entity_a, entity_b) in collision_pairs.0.iter().cloned() {
+        if let Ok(((mut pos_a, box_a, mass_a), (mut pos_b, box_b, mass_b))) =
+            query.get_pair_mut(entity_a, entity_b)
+        {
+            if let Some(Contact {
+                normal,
+                penetration,
+            }) = contact::box_box(pos_a.0, box_a.size, pos_b.0, box_b.size)
+            {
+                constrain_body_positions(
+                    &mut pos_a,
+                    &mut pos_b,
+                    mass_a,
+                    mass_b,
+                    normal,
+                    penetration,
+                );
+                contacts.0.push((entity_a, entity_b, normal));
+            }
+        }
+    }
+}
+
+pub fn solve_pos_ball_box(
+    mut balls: Query<(&mut Pos, &CircleCollider, &Mass), Without<BoxCollider>>,
+    mut boxes: Query<(&mut Pos, &BoxCollider, &Mass), Without<CircleCollider>>,
+    mut contacts: ResMut<Contacts>,
+    collision_pairs: Res<CollisionPairs>,
+) {
+    for (entity_a, entity_b) in collision_pairs.0.iter().cloned() {
+        // the pair can be in any order, contacts are always stored as (ball, box)
+        let (ball_entity, box_entity) = if balls.get_mut(entity_a).is_ok() {
+            (entity_a, entity_b)
+        } else {
+            (entity_b, entity_a)
+        };
+        if let (Ok((mut pos_a, circle_a, mass_a)), Ok((mut pos_b, box_b, mass_b))) =
+            (balls.get_mut(ball_entity), boxes.get_mut(box_entity))
+        {
+            if let Some(Contact {
+                normal,
+                penetration,
+            }) = contact::ball_box(pos_a.0, circle_a.radius, pos_b.0, box_b.size)
+            {
+                constrain_body_positions(
+                    &mut pos_a,
+                    &mut pos_b,
+                    mass_a,
+                    mass_b,
+                    normal,
+                    penetration,
+                );
+                contacts.0.push((ball_entity, box_entity, normal));
+            }
+        }
+    }
+}
+
+pub fn solve_pos_distance_constraints(
+    particles: Query<(Entity, &SoftBodyParticle)>,
+    mut bodies: Query<(&mut Pos, &Mass)>,
+    constraints: Query<&DistanceConstraint>,
+) {
+    let lookup: HashMap<(u32, u32), Entity> = particles
+        .iter()
+        .map(|(entity, particle)| ((particle.body, particle.index), entity))
+        .collect();
+
+    for constraint in constraints.iter() {
+        let entity_a = lookup.get(&(constraint.body, constraint.a));
+        let entity_b = lookup.get(&(constraint.body, constraint.b));
+        if let (Some(entity_a), Some(entity_b)) = (entity_a, entity_b) {
+            if let Ok(((mut pos_a, mass_a), (mut pos_b, mass_b))) =
+                bodies.get_pair_mut(*entity_a, *entity_b)
+            {
+                constrain_distance(
+                    &mut pos_a,
+                    &mut pos_b,
+                    mass_a,
+                    mass_b,
+                    constraint.rest_length,
+                    constraint.compliance,
+                );
+            }
+        }
+    }
+}
+
+pub fn solve_pos_static_ball_ball(
+    mut dynamics: Query<(Entity, &mut Pos, &CircleCollider), With<Mass>>,
+    statics: Query<(Entity, &Pos, &CircleCollider), Without<Mass>>,
+    mut contacts: ResMut<StaticContacts>,
+) {
+    for (entity_a, mut pos_a, circle_a) in dynamics.iter_mut() {
+        for (entity_b, pos_b, circle_b) in statics.iter() {
+            if let Some(Contact {
+                normal,
+                penetration,
+            }) = contact::ball_ball(pos_a.0, circle_a.radius, pos_b.0, circle_b.radius)
+            {
+                constrain_body_position(&mut pos_a, normal, penetration);
+                contacts.0.push((entity_a, entity_b, normal));
+            }
+        }
+    }
+}
+
+pub fn solve_pos_static_box_ball(
+    mut dynamics: Query<(Entity, &mut Pos, &PrevPos, &CircleCollider), With<Mass>>,
+    statics: Query<(Entity, &Pos, &BoxCollider, Option<&OneWay>), Without<Mass>>,
+    mut contacts: ResMut<StaticContacts>,
+) {
+    for (entity_a, mut pos_a, prev_pos_a, circle_a) in dynamics.iter_mut() {
+        for (entity_b, pos_b, box_b, one_way) in statics.iter() {
+            if let Some(Contact {
+                normal,
+                penetration,
+            }) = contact::ball_box(pos_a.0, circle_a.radius, pos_b.0, box_b.size)
+            {
+                if one_way.is_some()
+                    && !lands_on_one_way(
+                        normal,
+                        prev_pos_a.0.y - circle_a.radius,
+                        pos_b.0.y + box_b.size.y / 2.,
+                    )
+                {
+                    continue;
+                }
+                constrain_body_position(&mut pos_a, normal, penetration);
+                contacts.0.push((entity_a, entity_b, normal));
+            }
+        }
+    }
+}
+
+pub fn solve_pos_static_box_box(
+    mut dynamics: Query<(Entity, &mut Pos, &PrevPos, &BoxCollider), With<Mass>>,
+    statics: Query<(Entity, &Pos, &BoxCollider, Option<&OneWay>), Without<Mass>>,
+    mut contacts: ResMut<StaticContacts>,
+) {
+    for (entity_a, mut pos_a, prev_pos_a, box_a) in dynamics.iter_mut() {
+        for (entity_b, pos_b, box_b, one_way) in statics.iter() {
+            if let Some(Contact {
+                normal,
+                penetration,
+            }) = contact::box_box(pos_a.0, box_a.size, pos_b.0, box_b.size)
+            {
+                if one_way.is_some()
+                    && !lands_on_one_way(
+                        normal,
+                        prev_pos_a.0.y - box_a.size.y / 2.,
+                        pos_b.0.y + box_b.size.y / 2.,
+                    )
+                {
+                    continue;
+                }
+                constrain_body_position(&mut pos_a, normal, penetration);
+                contacts.0.push((entity_a, entity_b, normal));
+            }
+        }
+    }
+}
+
+pub fn update_vel(mut query: Query<(&Pos, &PrevPos, &mut Vel)>) {
+    debug!("  update_vel");
+    for (pos, prev_pos, mut vel) in query.iter_mut() {
+        vel.0 = (pos.0 - prev_pos.0) / SUB_DT;
+    }
+}
+
+pub fn solve_vel(
+    mut query: Query<(
+        &mut Vel,
+        &PreSolveVel,
+        &Mass,
+        &Restitution,
+        Option<&Friction>,
+    )>,
+    contacts: Res<Contacts>,
+) {
+    debug!("  solve_vel");
+    for (entity_a, entity_b, n) in contacts.0.iter().cloned() {
+        let (
+            (mut vel_a, pre_solve_vel_a, mass_a, restitution_a, friction_a),
+            (mut vel_b, pre_solve_vel_b, mass_b, restitution_b, friction_b),
+        ) = query.get_pair_mut(entity_a, entity_b).unwrap();
+        let normal_impulse = constrain_body_velocities(
+            &mut vel_a,
+            &mut vel_b,
+            pre_solve_vel_a,
+            pre_solve_vel_b,
+            mass_a,
+            mass_b,
+            restitution_a,
+            restitution_b,
+            n,
+        );
+        let friction = contact_friction(friction_a, friction_b);
+        constrain_body_frictions(
+            &mut vel_a,
+            &mut vel_b,
+            mass_a,
+            mass_b,
+            friction,
+            normal_impulse,
+            n,
+        );
+    }
+}
+
+pub fn solve_vel_statics(
+    mut dynamics: Query<(&mut Vel, &PreSolveVel, &Restitution, Option<&Friction>), With<Mass>>,
+    statics: Query<(&Restitution, Option<&Friction>), Without<Mass>>,
+    contacts: Res<StaticContacts>,
+) {
+    for (entity_a, entity_b, n) in contacts.0.iter().cloned() {
+        let (mut vel_a, pre_solve_vel_a, restitution_a, friction_a) =
+            dynamics.get_mut(entity_a).unwrap();
+        let (restitution_b, friction_b) = statics.get(entity_b).unwrap();
+        let normal_impulse =
+            constrain_body_velocity(&mut vel_a, pre_solve_vel_a, restitution_a, restitution_b, n);
+        let friction = contact_friction(friction_a, friction_b);
+        let vel_change = friction_vel_change(vel_a.0, n, friction, normal_impulse);
+        vel_a.0 += vel_change;
+    }
+}
+
+/// Copies positions from the physics world to bevy Transforms
+pub fn sync_transforms(mut query: Query<(&mut bevy::transform::components::Transform, &Pos)>) {
+    debug!("sync_transforms");
+    for (mut transform, pos) in query.iter_mut() {
+        let z = transform.translation.z;
+        transform.translation = pos.0.extend(z);
+    }
+}
+
+// Helpers, not systems:
+
+fn constrain_body_positions(
+    pos_a: &mut Pos,
+    pos_b: &mut Pos,
+    mass_a: &Mass,
+    mass_b: &Mass,
+    n: Vec2,
+    penetration_depth: f32,
+) {
+    let w_a = 1. / mass_a.0;
+    let w_b = 1. / mass_b.0;
+    let w_sum = w_a + w_b;
+    let pos_impulse = n * (-penetration_depth / w_sum);
+    pos_a.0 += pos_impulse * w_a;
+    pos_b.0 -= pos_impulse * w_b;
+}
+
+fn constrain_distance(
+    pos_a: &mut Pos,
+    pos_b: &mut Pos,
+    mass_a: &Mass,
+    mass_b: &Mass,
+    rest_length: f32,
+    compliance: f32,
+) {
+    let ab = pos_b.0 - pos_a.0;
+    let length = ab.length();
+    if length <= f32::EPSILON {
+        return;
+    }
+    let n = ab / length;
+    let c = length - rest_length;
+    let w_a = 1. / mass_a.0;
+    let w_b = 1. / mass_b.0;
+    let alpha = compliance / (SUB_DT * SUB_DT);
+    let delta_lambda = -c / (w_a + w_b + alpha);
+    pos_a.0 -= n * delta_lambda * w_a;
+    pos_b.0 += n * delta_lambda * w_b;
+}
+
+/// A one-way platform only stops bodies that come down onto it from above.
+/// The normal points from the dynamic body to the platform.
+fn lands_on_one_way(normal: Vec2, prev_bottom: f32, platform_top: f32) -> bool {
+    normal.y < 0. && prev_bottom >= platform_top - ONE_WAY_MARGIN
+}
+
+// todo: just inline this, it's not worth a function
+fn constrain_body_position(pos: &mut Pos, n: Vec2, penetration_depth: f32) {
+    pos.0 -= n * penetration_depth;
+}
+
+fn constrain_body_velocities(
+    vel_a: &mut Vel,
+    vel_b: &mut Vel,
+    pre_solve_vel_a: &PreSolveVel,
+    pre_solve_vel_b: &PreSolveVel,
+    mass_a: &Mass,
+    mass_b: &Mass,
+    restitution_a: &Restitution,
+    restitution_b: &Restitution,
+    n: Vec2,
+) -> f32 {
+    let pre_solve_relative_vel = pre_solve_vel_a.0 - pre_solve_vel_b.0;
+    let pre_solve_normal_vel = Vec2::dot(pre_solve_relative_vel, n);
+
+    let relative_vel = vel_a.0 - vel_b.0;
+    let normal_vel = Vec2::dot(relative_vel, n);
+    let restitution = (restitution_a.0 + restitution_b.0) / 2.;
+
+    let w_a = 1. / mass_a.0;
+    let w_b = 1. / mass_b.0;
+    let w_sum = w_a + w_b;
+
+    let restitution_velocity = (-restitution * pre_solve_normal_vel).min(0.);
+    let vel_impulse = n * ((-normal_vel + restitution_velocity) / w_sum);
+
+    vel_a.0 += vel_impulse * w_a;
+    vel_b.0 -= vel_impulse * w_b;
+
+    // how much the contact slowed the bodies down along the normal during the whole step
+    (pre_solve_normal_vel - restitution_velocity).max(0.)
+}
+
+fn constrain_body_velocity(
+    vel_a: &mut Vel,
+    pre_solve_vel_a: &PreSolveVel,
+    restitution_a: &Restitution,
+    restitution_b: &Restitution,
+    n: Vec2,
+) -> f32 {
+    let pre_solve_normal_vel = Vec2::dot(pre_solve_vel_a.0, n);
+    let normal_vel = Vec2::dot(vel_a.0, n);
+    let restitution = (restitution_a.0 + restitution_b.0) / 2.;
+    let restitution_velocity = (-restitution * pre_solve_normal_vel).min(0.);
+    vel_a.0 += n * (-normal_vel + restitution_velocity);
+    (pre_solve_normal_vel - restitution_velocity).max(0.)
+}
+
+fn contact_friction(friction_a: Option<&Friction>, friction_b: Option<&Friction>) -> f32 {
+    let friction = |f: Option<&Friction>| f.map_or(0., |f| f.0);
+    (friction(friction_a) + friction(friction_b)) / 2.
+}
+
+/// Coulomb friction: the change of the relative velocity that removes its tangential part,
+/// but at most `friction` times the relative velocity the contact removed along the normal.
+fn friction_vel_change(relative_vel: Vec2, n: Vec2, friction: f32, normal_impulse: f32) -> Vec2 {
+    let tangent_vel = relative_vel - n * Vec2::dot(relative_vel, n);
+    let tangent_speed = tangent_vel.length();
+    if tangent_speed <= f32::EPSILON {
+        return Vec2::ZERO;
+    }
+    -tangent_vel / tangent_speed * (friction * normal_impulse).min(tangent_speed)
+}
+
+fn constrain_body_frictions(
+    vel_a: &mut Vel,
+    vel_b: &mut Vel,
+    mass_a: &Mass,
+    mass_b: &Mass,
+    friction: f32,
+    normal_impulse: f32,
+    n: Vec2,
+) {
+    let w_a = 1. / mass_a.0;
+    let w_b = 1. / mass_b.0;
+    let w_sum = w_a + w_b;
+
+    let vel_change = friction_vel_change(vel_a.0 - vel_b.0, n, friction, normal_impulse);
+    vel_a.0 += vel_change * (w_a / w_sum);
+    vel_b.0 -= vel_change * (w_b / w_sum);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn vec2(range: f32) -> impl Strategy<Value = Vec2> {
+        (-range..range, -range..range).prop_map(|(x, y)| Vec2::new(x, y))
+    }
+
+    fn normal() -> impl Strategy<Value = Vec2> {
+        (0f32..std::f32::consts::TAU).prop_map(|angle| Vec2::new(angle.cos(), angle.sin()))
+    }
+
+    fn approx_eq(a: Vec2, b: Vec2) -> bool {
+        (a - b).length() < 1e-2
+    }
+
+    proptest! {
+        #[test]
+        fn positions_conserve_center_of_mass(
+            a in vec2(10.), b in vec2(10.), m_a in 0.1f32..10., m_b in 0.1f32..10., n in normal(), penetration in 0f32..2.,
+        ) {
+            let (mut pos_a, mut pos_b) = (Pos(a), Pos(b));
+            constrain_body_positions(&mut pos_a, &mut pos_b, &Mass(m_a), &Mass(m_b), n, penetration);
+
+            let com_before = (a * m_a + b * m_b) / (m_a + m_b);
+            let com_after = (pos_a.0 * m_a + pos_b.0 * m_b) / (m_a + m_b);
+            prop_assert!(approx_eq(com_before, com_after));
+        }
+
+        #[test]
+        fn positions_resolve_penetration(
+            a in vec2(10.), b in vec2(10.), m_a in 0.1f32..10., m_b in 0.1f32..10., n in normal(), penetration in 0f32..2.,
+        ) {
+            let (mut pos_a, mut pos_b) = (Pos(a), Pos(b));
+            constrain_body_positions(&mut pos_a, &mut pos_b, &Mass(m_a), &Mass(m_b), n, penetration);
+
+            // the bodies are pushed apart along the normal by exactly the penetration depth
+            let separation = (pos_b.0 - pos_a.0).dot(n) - (b - a).dot(n);
+            prop_assert!((separation - penetration).abs() < 1e-3);
+            // the lighter body moves further
+            let moved_a = (pos_a.0 - a).length();
+            let moved_b = (pos_b.0 - b).length();
+            prop_assert!((moved_a * m_a - moved_b * m_b).abs() < 1e-2);
+        }
+
+        #[test]
+        fn ball_box_positions_resolve_penetration(
+            ball in vec2(10.), offset in vec2(1.), radius in 0.1f32..2., size in vec2(5.),
+            m_a in 0.1f32..10., m_b in 0.1f32..10.,
+        ) {
+            // keep the box somewhere around the ball, so most cases are in contact
+            let size = size.abs() + Vec2::splat(0.1);
+            let b = ball + offset * (size / 2. + Vec2::splat(radius));
+            let contact = contact::ball_box(ball, radius, b, size);
+            prop_assume!(contact.is_some());
+            let Contact { normal, penetration } = contact.unwrap();
+
+            let (mut pos_a, mut pos_b) = (Pos(ball), Pos(b));
+            constrain_body_positions(&mut pos_a, &mut pos_b, &Mass(m_a), &Mass(m_b), normal, penetration);
+
+            // the ball ends up touching the box, not inside it and not flung away from it
+            let penetration_after = contact::ball_box(pos_a.0, radius, pos_b.0, size).map_or(0., |c| c.penetration);
+            prop_assert!(penetration_after < 1e-3);
+            prop_assert!((pos_a.0 - ball).length() <= penetration + 1e-3);
+            prop_assert!((pos_b.0 - b).length() <= penetration + 1e-3);
+            let com_before = (ball * m_a + b * m_b) / (m_a + m_b);
+            let com_after = (pos_a.0 * m_a + pos_b.0 * m_b) / (m_a + m_b);
+            prop_assert!(approx_eq(com_before, com_after));
+        }
+
+        #[test]
+        fn rigid_distance_constraint_restores_rest_length(
+            a in vec2(10.), b in vec2(10.), m_a in 0.1f32..10., m_b in 0.1f32..10., rest_length in 0.1f32..10.,
+        ) {
+            prop_assume!(a.distance(b) > 1e-2);
+            let (mut pos_a, mut pos_b) = (Pos(a), Pos(b));
+            constrain_distance(&mut pos_a, &mut pos_b, &Mass(m_a), &Mass(m_b), rest_length, 0.);
+
+            prop_assert!((pos_a.0.distance(pos_b.0) - rest_length).abs() < 1e-3);
+            let com_before = (a * m_a + b * m_b) / (m_a + m_b);
+            let com_after = (pos_a.0 * m_a + pos_b.0 * m_b) / (m_a + m_b);
+            prop_assert!(approx_eq(com_before, com_after));
+        }
+
+        #[test]
+        fn soft_distance_constraint_moves_towards_rest_length(
+            a in vec2(10.), b in vec2(10.), rest_length in 0.1f32..10., compliance in 0.0001f32..0.1,
+        ) {
+            prop_assume!(a.distance(b) > 1e-2);
+            let (mut pos_a, mut pos_b) = (Pos(a), Pos(b));
+            constrain_distance(&mut pos_a, &mut pos_b, &Mass(1.), &Mass(1.), rest_length, compliance);
+
+            let error_before = (a.distance(b) - rest_length).abs();
+            let error_after = (pos_a.0.distance(pos_b.0) - rest_length).abs();
+            prop_assert!(error_after <= error_before + 1e-4);
+        }
+
+        #[test]
+        fn static_position_resolves_penetration(a in vec2(10.), n in normal(), penetration in 0f32..2.) {
+            let mut pos = Pos(a);
+            constrain_body_position(&mut pos, n, penetration);
+            prop_assert!(approx_eq(pos.0, a - n * penetration));
+        }
+
+        #[test]
+        fn velocities_conserve_momentum(
+            v_a in vec2(100.), v_b in vec2(100.), m_a in 0.1f32..10., m_b in 0.1f32..10., n in normal(),
+            e_a in 0f32..1., e_b in 0f32..1.,
+        ) {
+            let (mut vel_a, mut vel_b) = (Vel(v_a), Vel(v_b));
+            constrain_body_velocities(
+                &mut vel_a, &mut vel_b, &PreSolveVel(v_a), &PreSolveVel(v_b),
+                &Mass(m_a), &Mass(m_b), &Restitution(e_a), &Restitution(e_b), n,
+            );
+
+            let momentum_before = v_a * m_a + v_b * m_b;
+            let momentum_after = vel_a.0 * m_a + vel_b.0 * m_b;
+            prop_assert!((momentum_before - momentum_after).length() < 1e-2 * momentum_before.length().max(1.));
+        }
+
+        #[test]
+        fn velocities_apply_restitution(
+            v_a in vec2(100.), v_b in vec2(100.), m_a in 0.1f32..10., m_b in 0.1f32..10., n in normal(),
+            e_a in 0f32..1., e_b in 0f32..1.,
+        ) {
+            let (mut vel_a, mut vel_b) = (Vel(v_a), Vel(v_b));
+            constrain_body_velocities(
+                &mut vel_a, &mut vel_b, &PreSolveVel(v_a), &PreSolveVel(v_b),
+                &Mass(m_a), &Mass(m_b), &Restitution(e_a), &Restitution(e_b), n,
+            );
+
+            // the normal points from a to b, so a positive normal velocity means the bodies approach
+            let pre_solve_normal_vel = (v_a - v_b).dot(n);
+            let expected = (-(e_a + e_b) / 2. * pre_solve_normal_vel).min(0.);
+            let normal_vel = (vel_a.0 - vel_b.0).dot(n);
+            prop_assert!((normal_vel - expected).abs() < 1e-2);
+            // tangential velocities are untouched
+            let tangent = n.perp();
+            prop_assert!(((vel_a.0 - v_a).dot(tangent)).abs() < 1e-2);
+            prop_assert!(((vel_b.0 - v_b).dot(tangent)).abs() < 1e-2);
+        }
+
+        #[test]
+        fn frictions_slow_down_sliding_without_reversing_it(
+            v_a in vec2(100.), v_b in vec2(100.), m_a in 0.1f32..10., m_b in 0.1f32..10., n in normal(),
+            friction in 0f32..1., normal_impulse in 0f32..100.,
+        ) {
+            let (mut vel_a, mut vel_b) = (Vel(v_a), Vel(v_b));
+            constrain_body_frictions(&mut vel_a, &mut vel_b, &Mass(m_a), &Mass(m_b), friction, normal_impulse, n);
+
+            let tangent = n.perp();
+            let sliding_before = (v_a - v_b).dot(tangent);
+            let sliding_after = (vel_a.0 - vel_b.0).dot(tangent);
+            prop_assert!(sliding_after.abs() <= sliding_before.abs() + 1e-3);
+            prop_assert!(sliding_after * sliding_before >= -1e-3);
+            prop_assert!((sliding_before - sliding_after).abs() <= friction * normal_impulse + 1e-3);
+            // normal velocities are untouched and momentum is conserved
+            prop_assert!(((vel_a.0 - vel_b.0).dot(n) - (v_a - v_b).dot(n)).abs() < 1e-2);
+            let momentum_before = v_a * m_a + v_b * m_b;
+            let momentum_after = vel_a.0 * m_a + vel_b.0 * m_b;
+            prop_assert!((momentum_before - momentum_after).length() < 1e-2 * momentum_before.length().max(1.));
+        }
+
+        #[test]
+        fn static_velocity_applies_restitution(v in vec2(100.), n in normal(), e_a in 0f32..1., e_b in 0f32..1.) {
+            let mut vel = Vel(v);
+            constrain_body_velocity(&mut vel, &PreSolveVel(v), &Restitution(e_a), &Restitution(e_b), n);
+
+            let expected = (-(e_a + e_b) / 2. * v.dot(n)).min(0.);
+            prop_assert!((vel.0.dot(n) - expected).abs() < 1e-2);
+            prop_assert!(((vel.0 - v).dot(n.perp())).abs() < 1e-2);
+        }
+    }
+}
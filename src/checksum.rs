@@ -1,10 +1,8 @@
 use bevy::prelude::*;
 use bevy_ggrs::Rollback;
+use physics::{components::Vel, prelude::Pos};
 
-use crate::{
-    physics::{components::Vel, prelude::Pos},
-    round::prelude::{Attacker, Cake, Crosshair, Splat},
-};
+use crate::round::prelude::{Attacker, CakeParticle, Crosshair, Splat};
 
 #[derive(Default, Reflect, Hash, Component)]
 #[reflect(Hash)]
@@ -35,16 +33,10 @@ pub fn checksum_attackers(
 }
 
 pub fn checksum_cakes(
-    mut query: Query<(&Transform, &Vel, &Pos, &mut Checksum), (With<Cake>, With<Rollback>)>,
+    mut query: Query<(&Vel, &Pos, &mut Checksum), (With<CakeParticle>, With<Rollback>)>,
 ) {
-    for (t, v, p, mut checksum) in query.iter_mut() {
-        let translation = t.translation;
-        let mut bytes = Vec::with_capacity(28);
-        bytes.extend_from_slice(&translation.x.to_le_bytes());
-        bytes.extend_from_slice(&translation.y.to_le_bytes());
-        bytes.extend_from_slice(&translation.z.to_le_bytes()); // this z will probably never matter, but removing it probably also will not matter...
-
-        // TODO: checksum more physics types here
+    for (v, p, mut checksum) in query.iter_mut() {
+        let mut bytes = Vec::with_capacity(16);
         bytes.extend_from_slice(&v.0.x.to_le_bytes());
         bytes.extend_from_slice(&v.0.y.to_le_bytes());
 
@@ -71,15 +63,14 @@ pub fn checksum_crosshair(
     }
 }
 
-pub fn checksum_splat(
-    mut query: Query<(&Transform, &mut Checksum), (With<Splat>, With<Rollback>)>,
-) {
-    for (t, mut checksum) in query.iter_mut() {
+pub fn checksum_splat(mut query: Query<(&Transform, &Splat, &mut Checksum), With<Rollback>>) {
+    for (t, splat, mut checksum) in query.iter_mut() {
         let translation = t.translation;
-        let mut bytes = Vec::with_capacity(12);
+        let mut bytes = Vec::with_capacity(16);
         bytes.extend_from_slice(&translation.x.to_le_bytes());
         bytes.extend_from_slice(&translation.y.to_le_bytes());
         bytes.extend_from_slice(&translation.z.to_le_bytes()); // this z will probably never matter, but removing it probably also will not matter...
+        bytes.extend_from_slice(&splat.dirt.to_le_bytes());
 
         // naive checksum implementation
         checksum.value = fletcher16(&bytes);
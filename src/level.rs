@@ -0,0 +1,177 @@
+use std::path::PathBuf;
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use bevy_asset_loader::AssetCollection;
+use serde::Deserialize;
+
+use crate::{SCREEN_X, SCREEN_Y};
+
+#[derive(AssetCollection)]
+pub struct LevelAssets {
+    #[asset(path = "levels/default.level")]
+    pub default_level: Handle<Level>,
+}
+
+/// An axis aligned box in world coordinates
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct LevelBox {
+    pub pos: Vec2, // center
+    pub size: Vec2,
+    #[serde(default)]
+    pub one_way: bool, // only for platforms, janitors can jump through these from below
+}
+
+/// Something in the arena that gets in the way of janitors and the fortress alike
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum HazardKind {
+    Conveyor(f32), // carries janitors and splats on top of it, speed in pixels per second
+    Fan(Vec2),     // pushes everything flying through it, mostly cakes
+    Puddle,        // janitors slip on it
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct LevelHazard {
+    pub pos: Vec2, // center
+    pub size: Vec2,
+    pub kind: HazardKind,
+}
+
+/// A level, written in RON. Everything is spawned in the order it is listed in,
+/// so all peers end up with the same rollback ids.
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "2f6f54d3-6a0e-4d5c-9a5d-3c1b8e5f7a21"]
+pub struct Level {
+    pub background: String, // path to the background image
+    pub statics: Vec<LevelBox>,
+    pub platforms: Vec<LevelBox>,
+    pub janitor_spawns: Vec<Vec2>, // used in order, wrapping around if there are more janitors
+    pub fortress_spawn: Vec2,
+    pub fortress_track: (f32, f32), // x range the fortress can roll along
+    #[serde(default)]
+    pub hazards: Vec<LevelHazard>,
+    #[serde(skip)]
+    pub background_image: Handle<Image>,
+}
+
+/// The top edge of something janitors can stand on
+#[derive(Debug, Clone, Copy)]
+pub struct Floor {
+    pub y: f32,
+    pub min_x: f32,
+    pub max_x: f32,
+}
+
+impl Level {
+    /// Parses a level and checks it has everything a round needs
+    pub fn from_ron(bytes: &[u8]) -> Result<Self, anyhow::Error> {
+        // a missing fortress spawn is already caught here, it has no default
+        let level: Level = ron::de::from_bytes(bytes)?;
+        if level.janitor_spawns.is_empty() {
+            anyhow::bail!("Level has no janitor spawns.");
+        }
+        Ok(level)
+    }
+
+    pub fn janitor_spawn(&self, index: usize) -> Vec2 {
+        self.janitor_spawns[index % self.janitor_spawns.len()]
+    }
+
+    // everything with a collider, as (center, size)
+    fn solids(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        let boxes = self.statics.iter().chain(self.platforms.iter());
+        let conveyors = self
+            .hazards
+            .iter()
+            .filter(|h| matches!(h.kind, HazardKind::Conveyor(..)));
+        boxes
+            .map(|b| (b.pos, b.size))
+            .chain(conveyors.map(|h| (h.pos, h.size)))
+    }
+
+    /// Top edges of the level inside the visible area, in the order they are listed in
+    pub fn floors(&self) -> Vec<Floor> {
+        self.solids()
+            .map(|(pos, size)| Floor {
+                y: pos.y + size.y / 2.,
+                min_x: (pos.x - size.x / 2.).max(-SCREEN_X / 4.),
+                max_x: (pos.x + size.x / 2.).min(SCREEN_X / 4.),
+            })
+            .filter(|f| f.min_x < f.max_x && f.y.abs() < SCREEN_Y / 4.)
+            .collect()
+    }
+
+    /// Whether a box at that position would overlap anything with a collider
+    pub fn blocked(&self, pos: Vec2, size: Vec2) -> bool {
+        self.solids().any(|(solid_pos, solid_size)| {
+            let dist = (pos - solid_pos).abs();
+            dist.x < (size.x + solid_size.x) / 2. && dist.y < (size.y + solid_size.y) / 2.
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let mut level = Level::from_ron(bytes)?;
+            let background = AssetPath::new(PathBuf::from(&level.background), None);
+            level.background_image = load_context.get_handle(background.clone());
+            load_context.set_default_asset(LoadedAsset::new(level).with_dependency(background));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level_with(spawns: &str) -> String {
+        format!(
+            "(background: \"bg.png\", statics: [], platforms: [], {} fortress_track: (0., 1.))",
+            spawns
+        )
+    }
+
+    #[test]
+    fn loads_a_complete_level() {
+        let ron = level_with("janitor_spawns: [(0., 0.)], fortress_spawn: (1., 1.),");
+        let level = Level::from_ron(ron.as_bytes()).unwrap();
+        assert_eq!(level.janitor_spawn(3), Vec2::ZERO);
+        assert!(level.hazards.is_empty());
+    }
+
+    #[test]
+    fn rejects_a_level_without_janitor_spawns() {
+        let ron = level_with("janitor_spawns: [], fortress_spawn: (1., 1.),");
+        assert!(Level::from_ron(ron.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn rejects_a_level_without_fortress_spawn() {
+        let ron = level_with("janitor_spawns: [(0., 0.)],");
+        assert!(Level::from_ron(ron.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn the_default_level_loads() {
+        let bytes = include_bytes!("../assets/levels/default.level");
+        let level = Level::from_ron(bytes).unwrap();
+        assert!(!level.floors().is_empty());
+    }
+}
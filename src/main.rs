@@ -1,6 +1,6 @@
 mod checksum;
+mod level;
 mod menu;
-mod physics;
 mod round;
 
 use bevy::prelude::*;
@@ -8,18 +8,19 @@ use bevy_asset_loader::{AssetCollection, AssetLoader};
 use bevy_ggrs::GGRSPlugin;
 use checksum::{checksum_attackers, checksum_cakes, checksum_crosshair, checksum_splat, Checksum};
 use ggrs::Config;
+use level::{Level, LevelAssets, LevelLoader};
 use menu::{
     connect::{create_matchbox_socket, update_matchbox_socket},
     online::{update_lobby_btn, update_lobby_id, update_lobby_id_display},
 };
 use physics::{components::*, create_physics_stage, prelude::*};
-use round::prelude::*;
+use round::{prelude::*, GRAVITY};
 
 const ROLLBACK_SYSTEMS: &str = "rollback_systems";
 const CHECKSUM_UPDATE: &str = "checksum_update";
 const PHYSICS_UPDATE: &str = "physics_update";
 
-const NUM_PLAYERS: usize = 2;
+const NUM_PLAYERS: usize = 2; // default, can be changed in the match settings
 const FPS: usize = 60;
 const MAX_PREDICTION: usize = 12;
 const INPUT_DELAY: usize = 2;
@@ -41,6 +42,7 @@ pub enum AppState {
     MenuConnect,
     RoundLocal,
     RoundOnline,
+    Paused, // only on top of RoundLocal
     Win,
 }
 
@@ -49,6 +51,7 @@ enum SystemLabel {
     UpdateState,
     Input,
     Move,
+    Hazards,
     End,
 }
 
@@ -56,8 +59,6 @@ enum SystemLabel {
 pub struct MiscAssets {
     #[asset(path = "sprites/misc/title.png")]
     pub game_title: Handle<Image>,
-    #[asset(path = "sprites/misc/background.png")]
-    pub background: Handle<Image>,
     #[asset(path = "sprites/misc/cake.png")]
     pub cake: Handle<Image>,
     #[asset(path = "sprites/misc/splat1.png")]
@@ -125,6 +126,7 @@ fn main() {
         .with_collection::<FontAssets>()
         .with_collection::<AttackerAssets>()
         .with_collection::<DefenderAssets>()
+        .with_collection::<LevelAssets>()
         .build(&mut app);
 
     GGRSPlugin::<GGRSConfig>::new()
@@ -136,15 +138,34 @@ fn main() {
         .register_rollback_type::<AttackerState>()
         .register_rollback_type::<DefenderState>()
         .register_rollback_type::<AttackerControls>()
+        .register_rollback_type::<AttackerCooldowns>()
         .register_rollback_type::<DefenderControls>()
         .register_rollback_type::<FrameCount>()
         .register_rollback_type::<Checksum>()
         .register_rollback_type::<RoundState>()
         .register_rollback_type::<RoundData>()
+        .register_rollback_type::<MatchStats>()
+        .register_rollback_type::<MatchRng>()
+        .register_rollback_type::<DefenderAmmo>()
         .register_rollback_type::<Transform>()
         .register_rollback_type::<FacingDirection>()
         .register_rollback_type::<Cake>()
+        .register_rollback_type::<CakeParticle>()
+        .register_rollback_type::<CakeState>()
+        .register_rollback_type::<CakeType>()
+        .register_rollback_type::<CakeImpact>()
+        .register_rollback_type::<SplatSurface>()
         .register_rollback_type::<Splat>()
+        .register_rollback_type::<Platform>()
+        .register_rollback_type::<Conveyor>()
+        .register_rollback_type::<Puddle>()
+        .register_rollback_type::<Stun>()
+        .register_rollback_type::<Pickup>()
+        .register_rollback_type::<SpeedBoost>()
+        .register_rollback_type::<StunImmunity>()
+        .register_rollback_type::<AreaClean>()
+        .register_rollback_type::<TripleShot>()
+        .register_rollback_type::<BigSplats>()
         .register_rollback_type::<Crosshair>()
         .register_rollback_type::<ScreenTimer>()
         // physics types
@@ -153,7 +174,14 @@ fn main() {
         .register_rollback_type::<PrevPos>()
         .register_rollback_type::<PreSolveVel>()
         .register_rollback_type::<Restitution>()
+        .register_rollback_type::<Friction>()
         .register_rollback_type::<BoxCollider>()
+        .register_rollback_type::<OneWay>()
+        .register_rollback_type::<ForceZone>()
+        .register_rollback_type::<CircleCollider>()
+        .register_rollback_type::<CollisionLayers>()
+        .register_rollback_type::<SoftBodyParticle>()
+        .register_rollback_type::<DistanceConstraint>()
         .register_rollback_type::<Mass>()
         .register_rollback_type::<Aabb>()
         .register_rollback_type::<StaticContacts>()
@@ -201,6 +229,13 @@ fn main() {
                                 .with_run_criteria(on_round)
                                 .with_system(update_attacker_state)
                                 .with_system(update_defender_state)
+                                .with_system(update_cake_bodies)
+                                .with_system(spawn_pickups)
+                                .with_system(tick_pickup_effect::<SpeedBoost>)
+                                .with_system(tick_pickup_effect::<StunImmunity>)
+                                .with_system(tick_pickup_effect::<AreaClean>)
+                                .with_system(tick_pickup_effect::<TripleShot>)
+                                .with_system(tick_pickup_effect::<BigSplats>)
                                 .label(SystemLabel::UpdateState),
                         )
                         .with_system_set(
@@ -216,17 +251,27 @@ fn main() {
                                 .with_run_criteria(on_round)
                                 .with_system(move_attackers)
                                 .with_system(move_crosshair)
+                                .with_system(move_defender)
                                 .with_system(cake_collision)
+                                .with_system(settle_splats)
                                 .with_system(splat_cleaning)
+                                .with_system(collect_pickups)
                                 .label(SystemLabel::Move)
                                 .after(SystemLabel::Input),
                         )
+                        .with_system_set(
+                            SystemSet::new()
+                                .with_run_criteria(on_round)
+                                .with_system(move_on_conveyors)
+                                .label(SystemLabel::Hazards)
+                                .after(SystemLabel::Move),
+                        )
                         .with_system_set(
                             SystemSet::new()
                                 .with_run_criteria(on_round)
                                 .with_system(check_round_end)
                                 .label(SystemLabel::End)
-                                .after(SystemLabel::Move),
+                                .after(SystemLabel::Hazards),
                         )
                         // round end
                         .with_system_set(
@@ -234,6 +279,19 @@ fn main() {
                                 .after(SystemLabel::End)
                                 .with_run_criteria(on_round_end)
                                 .with_system(cleanup_round),
+                        )
+                        // surrender and disconnects can end the match at any time
+                        .with_system_set(
+                            SystemSet::new()
+                                .after(SystemLabel::End)
+                                .with_run_criteria(not_match_end)
+                                .with_system(check_forfeit),
+                        )
+                        // match end
+                        .with_system_set(
+                            SystemSet::new()
+                                .with_run_criteria(on_match_end)
+                                .with_system(check_rematch),
                         ),
                 )
                 .with_stage_after(
@@ -256,16 +314,21 @@ fn main() {
         ..Default::default()
     })
     .add_plugins(DefaultPlugins)
+    .add_asset::<Level>()
+    .init_asset_loader::<LevelLoader>()
     .add_state(AppState::AssetLoading)
     .insert_resource(ClearColor(Color::BLACK))
+    .init_resource::<MatchSettings>()
     // physics
-    .add_plugin(PhysicsPlugin)
+    .insert_resource(Gravity(Vec2::new(0., GRAVITY)))
+    .add_plugin(PhysicsPlugin::without_stage())
     // main menu
     .add_system_set(SystemSet::on_enter(AppState::MenuMain).with_system(menu::main::setup_ui))
     .add_system_set(
         SystemSet::on_update(AppState::MenuMain)
             .with_system(menu::main::btn_visuals)
-            .with_system(menu::main::btn_listeners),
+            .with_system(menu::main::btn_listeners)
+            .with_system(menu::main::update_settings_text),
     )
     .add_system_set(SystemSet::on_exit(AppState::MenuMain).with_system(menu::main::cleanup_ui))
     //online menu
@@ -301,18 +364,50 @@ fn main() {
     .add_system_set(
         SystemSet::on_update(AppState::Win)
             .with_system(menu::win::btn_visuals)
-            .with_system(menu::win::btn_listeners),
+            .with_system(menu::win::btn_listeners)
+            .with_system(menu::win::update_rematch_btn),
+    )
+    .add_system_set(
+        SystemSet::on_exit(AppState::Win)
+            .with_system(menu::win::cleanup_ui)
+            .with_system(cleanup_session),
     )
-    .add_system_set(SystemSet::on_exit(AppState::Win).with_system(menu::win::cleanup_ui))
     // local round
-    .add_system_set(SystemSet::on_enter(AppState::RoundLocal).with_system(setup_game))
+    .add_system_set(
+        SystemSet::on_enter(AppState::RoundLocal)
+            .with_system(setup_game)
+            .with_system(menu::main::create_synctest_session),
+    )
     .add_system_set(
         SystemSet::on_update(AppState::RoundLocal)
             .with_system(update_attacker_sprite)
             .with_system(update_defender_sprite)
-            .with_system(update_screen_timer),
+            .with_system(update_splat_sprite)
+            .with_system(update_crosshair_sprite)
+            .with_system(update_trajectory_preview)
+            .with_system(update_screen_timer)
+            .with_system(update_ammo_display)
+            .with_system(menu::pause::pause_local_game),
+    )
+    .add_system_set(
+        SystemSet::on_pause(AppState::RoundLocal).with_system(menu::pause::freeze_session),
+    )
+    .add_system_set(
+        SystemSet::on_resume(AppState::RoundLocal).with_system(menu::pause::unfreeze_session),
+    )
+    .add_system_set(
+        SystemSet::on_exit(AppState::RoundLocal)
+            .with_system(cleanup_game)
+            .with_system(cleanup_session),
+    )
+    // pause menu
+    .add_system_set(SystemSet::on_enter(AppState::Paused).with_system(menu::pause::setup_ui))
+    .add_system_set(
+        SystemSet::on_update(AppState::Paused)
+            .with_system(menu::pause::btn_visuals)
+            .with_system(menu::pause::btn_listeners),
     )
-    .add_system_set(SystemSet::on_exit(AppState::RoundLocal).with_system(cleanup_game))
+    .add_system_set(SystemSet::on_exit(AppState::Paused).with_system(menu::pause::cleanup_ui))
     // online round
     .add_system_set(
         SystemSet::on_enter(AppState::RoundOnline)
@@ -323,14 +418,16 @@ fn main() {
         SystemSet::on_update(AppState::RoundOnline)
             .with_system(update_attacker_sprite)
             .with_system(update_defender_sprite)
+            .with_system(update_splat_sprite)
+            .with_system(update_crosshair_sprite)
+            .with_system(update_trajectory_preview)
             .with_system(update_screen_timer)
+            .with_system(update_ammo_display)
             .with_system(handle_p2p_events)
             .with_system(update_connection_info)
             .with_system(update_connection_display),
     )
     .add_system_set(SystemSet::on_exit(AppState::RoundOnline).with_system(cleanup_game));
-    // ldtk loading TODO: move to assetLoader plugin?
-    //.add_startup_system(load_ldtk_level);
 
     #[cfg(target_arch = "wasm32")]
     {
@@ -1,15 +1,21 @@
+use std::time::Duration;
+
 use bevy::{prelude::*, tasks::IoTaskPool};
 use bevy_ggrs::SessionType;
 use ggrs::{PlayerHandle, PlayerType, SessionBuilder};
 use matchbox_socket::WebRtcSocket;
 
 use crate::{
+    round::prelude::{MatchRng, MatchSettings},
     AppState, FontAssets, GGRSConfig, BUTTON_TEXT, FPS, HOVERED_BUTTON, INPUT_DELAY,
-    MAX_PREDICTION, NORMAL_BUTTON, NUM_PLAYERS, PRESSED_BUTTON,
+    MAX_PREDICTION, NORMAL_BUTTON, PRESSED_BUTTON,
 };
 
 //const MATCHBOX_ADDR: &str = "ws://127.0.0.1:3536";
 const MATCHBOX_ADDR: &str = "wss://match.gschup.dev";
+// peers that don't respond for this long are disconnected and forfeit the match
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const DISCONNECT_NOTIFY_DELAY: Duration = Duration::from_secs(1);
 
 #[derive(Component)]
 pub struct MenuConnectUI;
@@ -44,13 +50,14 @@ pub fn update_matchbox_socket(
     commands: Commands,
     mut state: ResMut<State<AppState>>,
     mut socket_res: ResMut<Option<WebRtcSocket>>,
+    settings: Res<MatchSettings>,
 ) {
     if let Some(socket) = socket_res.as_mut() {
         socket.accept_new_connections();
-        if socket.players().len() >= NUM_PLAYERS {
+        if socket.players().len() >= settings.num_players {
             // take the socket
             let socket = socket_res.as_mut().take().unwrap();
-            create_ggrs_session(commands, socket);
+            create_ggrs_session(commands, socket, settings.num_players);
             state
                 .set(AppState::RoundOnline)
                 .expect("Could not change state.");
@@ -181,14 +188,16 @@ pub fn cleanup_ui(query: Query<Entity, With<MenuConnectUI>>, mut commands: Comma
     }
 }
 
-fn create_ggrs_session(mut commands: Commands, socket: WebRtcSocket) {
+fn create_ggrs_session(mut commands: Commands, socket: WebRtcSocket, num_players: usize) {
     // create a new ggrs session
     let mut sess_build = SessionBuilder::<GGRSConfig>::new()
-        .with_num_players(NUM_PLAYERS)
+        .with_num_players(num_players)
         .with_max_prediction_window(MAX_PREDICTION)
         .with_fps(FPS)
         .expect("Invalid FPS")
-        .with_input_delay(INPUT_DELAY);
+        .with_input_delay(INPUT_DELAY)
+        .with_disconnect_timeout(DISCONNECT_TIMEOUT)
+        .with_disconnect_notify_delay(DISCONNECT_NOTIFY_DELAY);
 
     // add players
     let mut handles = Vec::new();
@@ -201,6 +210,18 @@ fn create_ggrs_session(mut commands: Commands, socket: WebRtcSocket) {
             .expect("Invalid player added.");
     }
 
+    // every peer knows all the ids, so they agree on the seed without sending it around
+    let mut peer_ids: Vec<String> = socket
+        .players()
+        .into_iter()
+        .filter_map(|player_type| match player_type {
+            PlayerType::Remote(id) => Some(id),
+            _ => None,
+        })
+        .collect();
+    peer_ids.push(socket.id().clone());
+    commands.insert_resource(MatchRng::from_peers(peer_ids));
+
     // start the GGRS session
     let sess = sess_build
         .start_p2p_session(socket)
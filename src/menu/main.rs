@@ -3,8 +3,9 @@ use bevy_ggrs::SessionType;
 use ggrs::{PlayerType, SessionBuilder};
 
 use crate::{
+    round::prelude::{MatchRng, MatchSettings},
     AppState, FontAssets, GGRSConfig, MiscAssets, BUTTON_TEXT, CHECK_DISTANCE, FPS, HOVERED_BUTTON,
-    INPUT_DELAY, MAX_PREDICTION, NORMAL_BUTTON, NUM_PLAYERS, PRESSED_BUTTON,
+    INPUT_DELAY, MAX_PREDICTION, NORMAL_BUTTON, PRESSED_BUTTON,
 };
 
 use super::connect::LocalHandles;
@@ -16,13 +17,51 @@ pub struct MenuMainUI;
 pub enum MenuMainBtn {
     OnlineMatch,
     LocalMatch,
+    NumPlayers,
+    JanitorInteraction,
+    BestOf,
+    RoundLength,
+    TeamMode,
+    TieBreak,
+    AimMode,
     Quit,
 }
 
+#[derive(Component)]
+pub enum SettingsText {
+    NumPlayers,
+    JanitorInteraction,
+    BestOf,
+    RoundLength,
+    TeamMode,
+    TieBreak,
+    AimMode,
+}
+
+impl SettingsText {
+    fn label(&self, settings: &MatchSettings) -> String {
+        match self {
+            SettingsText::NumPlayers => format!("Players: {}", settings.num_players),
+            SettingsText::JanitorInteraction => {
+                format!("Janitors: {}", settings.janitor_interaction)
+            }
+            SettingsText::BestOf => format!("Best of {}", settings.best_of),
+            SettingsText::RoundLength => format!("Round: {}s", settings.round_length / 60),
+            SettingsText::TieBreak => format!("Tie: {}", settings.tie_break),
+            SettingsText::AimMode => format!("Aim: {}", settings.aim_mode),
+            SettingsText::TeamMode => match settings.team_mode {
+                true => "Mode: Teams".to_owned(),
+                false => "Mode: Free for all".to_owned(),
+            },
+        }
+    }
+}
+
 pub fn setup_ui(
     mut commands: Commands,
     image_assets: Res<MiscAssets>,
     font_assets: Res<FontAssets>,
+    settings: Res<MatchSettings>,
 ) {
     // ui camera
     commands
@@ -118,6 +157,32 @@ pub fn setup_ui(
                 })
                 .insert(MenuMainBtn::LocalMatch);
 
+            // match settings
+            spawn_settings_row(
+                parent,
+                &font_assets,
+                &settings,
+                vec![
+                    (MenuMainBtn::NumPlayers, SettingsText::NumPlayers),
+                    (MenuMainBtn::TeamMode, SettingsText::TeamMode),
+                    (
+                        MenuMainBtn::JanitorInteraction,
+                        SettingsText::JanitorInteraction,
+                    ),
+                ],
+            );
+            spawn_settings_row(
+                parent,
+                &font_assets,
+                &settings,
+                vec![
+                    (MenuMainBtn::BestOf, SettingsText::BestOf),
+                    (MenuMainBtn::RoundLength, SettingsText::RoundLength),
+                    (MenuMainBtn::TieBreak, SettingsText::TieBreak),
+                    (MenuMainBtn::AimMode, SettingsText::AimMode),
+                ],
+            );
+
             // quit button
             parent
                 .spawn_bundle(ButtonBundle {
@@ -151,6 +216,69 @@ pub fn setup_ui(
         .insert(MenuMainUI);
 }
 
+fn spawn_settings_row(
+    parent: &mut ChildBuilder,
+    font_assets: &FontAssets,
+    settings: &MatchSettings,
+    btns: Vec<(MenuMainBtn, SettingsText)>,
+) {
+    parent
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            for (btn, text) in btns {
+                spawn_settings_btn(parent, font_assets, settings, btn, text);
+            }
+        });
+}
+
+fn spawn_settings_btn(
+    parent: &mut ChildBuilder,
+    font_assets: &FontAssets,
+    settings: &MatchSettings,
+    btn: MenuMainBtn,
+    text: SettingsText,
+) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(320.0), Val::Px(50.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                margin: Rect::all(Val::Px(8.)),
+                padding: Rect::all(Val::Px(8.)),
+                ..Default::default()
+            },
+            color: NORMAL_BUTTON.into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        text.label(settings),
+                        TextStyle {
+                            font: font_assets.default_font.clone(),
+                            font_size: 28.0,
+                            color: BUTTON_TEXT,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(text);
+        })
+        .insert(btn);
+}
+
 pub fn btn_visuals(
     mut interaction_query: Query<
         (&Interaction, &mut UiColor),
@@ -174,8 +302,8 @@ pub fn btn_visuals(
 
 pub fn btn_listeners(
     mut exit: EventWriter<AppExit>,
-    mut commands: Commands,
     mut state: ResMut<State<AppState>>,
+    mut settings: ResMut<MatchSettings>,
     mut interaction_query: Query<(&Interaction, &MenuMainBtn), Changed<Interaction>>,
 ) {
     for (interaction, btn) in interaction_query.iter_mut() {
@@ -187,11 +315,31 @@ pub fn btn_listeners(
                         .expect("Could not change state.");
                 }
                 MenuMainBtn::LocalMatch => {
-                    create_synctest_session(&mut commands);
                     state
                         .set(AppState::RoundLocal)
                         .expect("Could not change state.");
                 }
+                MenuMainBtn::NumPlayers => {
+                    settings.num_players = settings.next_num_players();
+                }
+                MenuMainBtn::JanitorInteraction => {
+                    settings.janitor_interaction = settings.janitor_interaction.next();
+                }
+                MenuMainBtn::BestOf => {
+                    settings.best_of = settings.next_best_of();
+                }
+                MenuMainBtn::RoundLength => {
+                    settings.round_length = settings.next_round_length();
+                }
+                MenuMainBtn::TeamMode => {
+                    settings.team_mode = !settings.team_mode;
+                }
+                MenuMainBtn::TieBreak => {
+                    settings.tie_break = settings.tie_break.next();
+                }
+                MenuMainBtn::AimMode => {
+                    settings.aim_mode = settings.aim_mode.next();
+                }
                 MenuMainBtn::Quit => {
                     exit.send(AppExit);
                 }
@@ -200,22 +348,40 @@ pub fn btn_listeners(
     }
 }
 
+pub fn update_settings_text(
+    settings: Res<MatchSettings>,
+    mut query: Query<(&mut Text, &SettingsText)>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for (mut text, label) in query.iter_mut() {
+        text.sections[0].value = label.label(&settings);
+    }
+}
+
 pub fn cleanup_ui(query: Query<Entity, With<MenuMainUI>>, mut commands: Commands) {
     for e in query.iter() {
         commands.entity(e).despawn_recursive();
     }
 }
 
-fn create_synctest_session(commands: &mut Commands) {
+// happens when entering the local round, so restarting the round also restarts the session
+pub fn create_synctest_session(
+    mut commands: Commands,
+    settings: Res<MatchSettings>,
+    time: Res<Time>,
+) {
+    let num_players = settings.num_players;
     let mut sess_build = SessionBuilder::<GGRSConfig>::new()
-        .with_num_players(NUM_PLAYERS)
+        .with_num_players(num_players)
         .with_max_prediction_window(MAX_PREDICTION)
         .with_fps(FPS)
         .expect("Invalid FPS")
         .with_input_delay(INPUT_DELAY)
         .with_check_distance(CHECK_DISTANCE);
 
-    for i in 0..NUM_PLAYERS {
+    for i in 0..num_players {
         sess_build = sess_build
             .add_player(PlayerType::Local, i)
             .expect("Could not add local player");
@@ -225,7 +391,9 @@ fn create_synctest_session(commands: &mut Commands) {
 
     commands.insert_resource(sess);
     commands.insert_resource(SessionType::SyncTestSession);
+    // nobody to agree with locally, any seed will do
+    commands.insert_resource(MatchRng::new(time.time_since_startup().as_nanos() as u64));
     commands.insert_resource(LocalHandles {
-        handles: (0..NUM_PLAYERS).collect(),
+        handles: (0..num_players).collect(),
     });
 }
@@ -1,4 +1,5 @@
 pub mod connect;
 pub mod main;
 pub mod online;
+pub mod pause;
 pub mod win;
@@ -1,8 +1,8 @@
 use bevy::prelude::*;
 
 use crate::{
-    AppState, FontAssets, BUTTON_TEXT, DISABLED_BUTTON, HOVERED_BUTTON, NORMAL_BUTTON,
-    PRESSED_BUTTON,
+    round::prelude::MatchSettings, AppState, FontAssets, BUTTON_TEXT, DISABLED_BUTTON,
+    HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON,
 };
 
 use super::connect::ConnectData;
@@ -254,6 +254,7 @@ pub fn btn_listeners(
     mut commands: Commands,
     mut state: ResMut<State<AppState>>,
     lobby_id: Res<LobbyID>,
+    settings: Res<MatchSettings>,
     mut interaction_query: Query<
         (&Interaction, &MenuOnlineBtn, Option<&ButtonEnabled>),
         Changed<Interaction>,
@@ -272,8 +273,9 @@ pub fn btn_listeners(
         if let Interaction::Clicked = *interaction {
             match btn {
                 MenuOnlineBtn::LobbyMatch => {
+                    // only match with players using the same settings
                     commands.insert_resource(ConnectData {
-                        lobby_id: format!("bevy{}", lobby_id.0),
+                        lobby_id: format!("bevy{}_{}", lobby_id.0, settings.room_tag()),
                     });
                     state
                         .set(AppState::MenuConnect)
@@ -281,7 +283,11 @@ pub fn btn_listeners(
                 }
                 MenuOnlineBtn::QuickMatch => {
                     commands.insert_resource(ConnectData {
-                        lobby_id: "bevy?next=2".to_owned(),
+                        lobby_id: format!(
+                            "bevy_{}?next={}",
+                            settings.room_tag(),
+                            settings.num_players
+                        ),
                     });
                     state
                         .set(AppState::MenuConnect)
@@ -0,0 +1,174 @@
+use bevy::prelude::*;
+use bevy_ggrs::SessionType;
+
+use crate::{AppState, FontAssets, BUTTON_TEXT, HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON};
+
+#[derive(Component)]
+pub struct PauseUI;
+
+#[derive(Component)]
+pub enum MenuPauseBtn {
+    Resume,
+    Restart,
+    Quit,
+}
+
+// only local matches can be paused, there is nobody to wait for us online
+pub fn pause_local_game(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        // otherwise, the pause menu would read the same key press and resume immediately
+        keyboard_input.clear_just_pressed(KeyCode::Escape);
+        state
+            .push(AppState::Paused)
+            .expect("Could not change state.");
+    }
+}
+
+// without a session type, the rollback schedule does not advance
+pub fn freeze_session(mut commands: Commands) {
+    commands.remove_resource::<SessionType>();
+}
+
+pub fn unfreeze_session(mut commands: Commands) {
+    commands.insert_resource(SessionType::SyncTestSession);
+}
+
+pub fn setup_ui(mut commands: Commands, font_assets: Res<FontAssets>) {
+    // ui camera
+    commands
+        .spawn_bundle(UiCameraBundle::default())
+        .insert(PauseUI);
+
+    // root node
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect::all(Val::Px(0.)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_content: AlignContent::Center,
+                align_items: AlignItems::Center,
+                align_self: AlignSelf::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0., 0., 0., 0.6).into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect::all(Val::Px(16.)),
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    "Paused",
+                    TextStyle {
+                        font: font_assets.default_font.clone(),
+                        font_size: 60.,
+                        color: BUTTON_TEXT,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+
+            for (btn, label) in [
+                (MenuPauseBtn::Resume, "Resume"),
+                (MenuPauseBtn::Restart, "Restart"),
+                (MenuPauseBtn::Quit, "Quit to Menu"),
+            ] {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(250.0), Val::Px(65.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            margin: Rect::all(Val::Px(16.)),
+                            padding: Rect::all(Val::Px(16.)),
+                            ..Default::default()
+                        },
+                        color: NORMAL_BUTTON.into(),
+                        ..Default::default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                label,
+                                TextStyle {
+                                    font: font_assets.default_font.clone(),
+                                    font_size: 40.0,
+                                    color: BUTTON_TEXT,
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        });
+                    })
+                    .insert(btn);
+            }
+        })
+        .insert(PauseUI);
+}
+
+pub fn btn_visuals(
+    mut interaction_query: Query<
+        (&Interaction, &mut UiColor),
+        (Changed<Interaction>, With<MenuPauseBtn>),
+    >,
+) {
+    for (interaction, mut color) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                *color = PRESSED_BUTTON.into();
+            }
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON.into();
+            }
+            Interaction::None => {
+                *color = NORMAL_BUTTON.into();
+            }
+        }
+    }
+}
+
+pub fn btn_listeners(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut state: ResMut<State<AppState>>,
+    mut interaction_query: Query<(&Interaction, &MenuPauseBtn), Changed<Interaction>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        state.pop().expect("Could not change state.");
+        return;
+    }
+
+    for (interaction, btn) in interaction_query.iter_mut() {
+        if let Interaction::Clicked = *interaction {
+            match btn {
+                MenuPauseBtn::Resume => {
+                    state.pop().expect("Could not change state.");
+                }
+                // replacing the whole state stack exits the round, so cleanup_game runs
+                MenuPauseBtn::Restart => {
+                    state
+                        .replace(AppState::RoundLocal)
+                        .expect("Could not change state.");
+                }
+                MenuPauseBtn::Quit => {
+                    state
+                        .replace(AppState::MenuMain)
+                        .expect("Could not change state.");
+                }
+            }
+        }
+    }
+}
+
+pub fn cleanup_ui(query: Query<Entity, With<PauseUI>>, mut commands: Commands) {
+    for e in query.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+}
@@ -1,24 +1,48 @@
 use bevy::prelude::*;
+use ggrs::P2PSession;
 
-use crate::{AppState, FontAssets, BUTTON_TEXT, HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON};
+use crate::{
+    round::prelude::{ConnectionInfo, ConnectionStatus},
+    AppState, FontAssets, GGRSConfig, BUTTON_TEXT, HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON,
+};
 
 #[derive(Component)]
 pub struct WinUI;
 
 #[derive(Component)]
 pub enum MenuWinBtn {
+    Rematch,
     Back,
 }
 
+#[derive(Component)]
+pub struct RematchText;
+
 pub struct MatchResult {
     pub result: String,
+    pub breakdown: String,
 }
 
+/// The local player wants a rematch, this is sent to the other players as input
+pub struct RematchRequest;
+
+/// All players agreed to a rematch, the session is kept alive for it
+pub struct Rematch;
+
 pub fn setup_ui(
     mut commands: Commands,
     match_data: Res<MatchResult>,
     font_assets: Res<FontAssets>,
+    session: Option<Res<P2PSession<GGRSConfig>>>,
+    con_info: Option<Res<ConnectionInfo>>,
 ) {
+    // a rematch needs every player, so it is only offered if nobody left
+    let can_rematch = session.is_some()
+        && !matches!(
+            con_info.map(|c| c.status),
+            Some(ConnectionStatus::Disconnected)
+        );
+
     // ui camera
     commands
         .spawn_bundle(UiCameraBundle::default())
@@ -59,6 +83,58 @@ pub fn setup_ui(
                 ),
                 ..Default::default()
             });
+            // stats breakdown
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    align_self: AlignSelf::Center,
+                    justify_content: JustifyContent::Center,
+                    margin: Rect::all(Val::Px(16.)),
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    match_data.breakdown.clone(),
+                    TextStyle {
+                        font: font_assets.default_font.clone(),
+                        font_size: 20.,
+                        color: BUTTON_TEXT,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+            // rematch button, only online the session is still alive
+            if can_rematch {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(250.0), Val::Px(65.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            margin: Rect::all(Val::Px(16.)),
+                            padding: Rect::all(Val::Px(16.)),
+                            ..Default::default()
+                        },
+                        color: NORMAL_BUTTON.into(),
+                        ..Default::default()
+                    })
+                    .with_children(|parent| {
+                        parent
+                            .spawn_bundle(TextBundle {
+                                text: Text::with_section(
+                                    "Rematch",
+                                    TextStyle {
+                                        font: font_assets.default_font.clone(),
+                                        font_size: 40.0,
+                                        color: BUTTON_TEXT,
+                                    },
+                                    Default::default(),
+                                ),
+                                ..Default::default()
+                            })
+                            .insert(RematchText);
+                    })
+                    .insert(MenuWinBtn::Rematch);
+            }
             // back to menu button
             parent
                 .spawn_bundle(ButtonBundle {
@@ -116,12 +192,20 @@ pub fn btn_visuals(
 }
 
 pub fn btn_listeners(
+    mut commands: Commands,
     mut state: ResMut<State<AppState>>,
     mut interaction_query: Query<(&Interaction, &MenuWinBtn), Changed<Interaction>>,
+    mut rematch_text: Query<&mut Text, With<RematchText>>,
 ) {
     for (interaction, btn) in interaction_query.iter_mut() {
         if let Interaction::Clicked = *interaction {
             match btn {
+                MenuWinBtn::Rematch => {
+                    commands.insert_resource(RematchRequest);
+                    for mut text in rematch_text.iter_mut() {
+                        text.sections[0].value = "Waiting...".to_owned();
+                    }
+                }
                 MenuWinBtn::Back => {
                     state
                         .set(AppState::MenuMain)
@@ -132,6 +216,36 @@ pub fn btn_listeners(
     }
 }
 
+/// Players can still leave while the others look at the win screen, then there is nobody to
+/// rematch with anymore
+pub fn update_rematch_btn(
+    mut commands: Commands,
+    session: Option<ResMut<P2PSession<GGRSConfig>>>,
+    con_info: Option<ResMut<ConnectionInfo>>,
+    buttons: Query<(Entity, &MenuWinBtn)>,
+) {
+    let (mut session, mut con_info) = match (session, con_info) {
+        (Some(session), Some(con_info)) => (session, con_info),
+        _ => return,
+    };
+    for event in session.events() {
+        info!("GGRS Event: {:?}", event);
+        if let ggrs::GGRSEvent::Disconnected { .. } = event {
+            con_info.status = ConnectionStatus::Disconnected;
+        }
+    }
+    if !matches!(con_info.status, ConnectionStatus::Disconnected) {
+        return;
+    }
+
+    commands.remove_resource::<RematchRequest>();
+    for (e, btn) in buttons.iter() {
+        if let MenuWinBtn::Rematch = btn {
+            commands.entity(e).despawn_recursive();
+        }
+    }
+}
+
 pub fn cleanup_ui(query: Query<Entity, With<WinUI>>, mut commands: Commands) {
     for e in query.iter() {
         commands.entity(e).despawn_recursive();
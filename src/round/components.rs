@@ -1,5 +1,7 @@
 use bevy::prelude::*;
 
+use super::{CakeParams, CAKE_PARAMS, CHARGE_FRAMES, MAX_CHARGE_POWER, MIN_CHARGE_POWER};
+
 #[derive(Default, Component, Reflect)]
 #[reflect(Component)]
 pub struct Attacker {
@@ -10,23 +12,154 @@ pub struct Attacker {
 #[reflect(Component)]
 pub struct Defender {
     pub handle: usize,
+    pub cake_type: CakeType, // what the fortress fires next
+    pub power: f32,          // of the shot being fired, set when the charge is released
+}
+
+// how long the janitor stays stunned after the last cake hit
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct Stun {
+    pub frames: usize,
 }
 
 #[derive(Default, Component, Reflect)]
 #[reflect(Component)]
 pub struct ScreenTimer;
 
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct AmmoUi;
+
 #[derive(Default, Component, Reflect)]
 #[reflect(Component)]
 pub struct Interlude;
 
 #[derive(Default, Component, Reflect)]
 #[reflect(Component)]
-pub struct Cake;
+pub struct Platform;
+
+// a static box that carries janitors and splats on top of it
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct Conveyor {
+    pub speed: f32,
+}
+
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct Puddle {
+    pub size: Vec2,
+}
+
+impl Puddle {
+    /// Whether feet at that position are standing in the puddle
+    pub fn under(&self, puddle_pos: Vec2, feet: Vec2) -> bool {
+        let dist = (feet - puddle_pos).abs();
+        dist.x < self.size.x / 2. && dist.y <= self.size.y / 2. + 1.
+    }
+}
+
+// root of a soft body cake, holds the sprite
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct Cake {
+    pub body: u32, // soft body id of the cake particles
+    pub bounces: u32,
+    pub power: f32, // how far the shot was charged, bigger shots make more splats
+}
+
+#[derive(Clone, Copy, Component, Reflect, Debug, PartialEq, Eq)]
+#[reflect(Component)]
+pub enum CakeType {
+    Normal,
+    Heavy,
+    Cluster,
+    Bouncy,
+    Crumb, // can't be fired, cluster cakes split into these
+}
+
+impl Default for CakeType {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
+impl CakeType {
+    pub fn params(&self) -> &'static CakeParams {
+        &CAKE_PARAMS[*self as usize]
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            CakeType::Normal => CakeType::Heavy,
+            CakeType::Heavy => CakeType::Cluster,
+            CakeType::Cluster => CakeType::Bouncy,
+            CakeType::Bouncy | CakeType::Crumb => CakeType::Normal,
+        }
+    }
+
+    pub fn prev(&self) -> Self {
+        match self {
+            CakeType::Normal | CakeType::Crumb => CakeType::Bouncy,
+            CakeType::Heavy => CakeType::Normal,
+            CakeType::Cluster => CakeType::Heavy,
+            CakeType::Bouncy => CakeType::Cluster,
+        }
+    }
+}
+
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct CakeParticle;
+
+// the usize counts the number of frames the cake has been in that state, bounces restart Flying
+#[derive(Clone, Copy, Component, Reflect, Debug)]
+#[reflect(Component)]
+pub enum CakeState {
+    Flying(usize),
+    Squish(usize),
+}
+
+impl Default for CakeState {
+    fn default() -> Self {
+        Self::Flying(0)
+    }
+}
+
+// where the cake first hit something
+#[derive(Default, Clone, Copy, Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct CakeImpact {
+    pub pos: Vec2,
+}
 
+// top edge of whatever the cake hit, the splats are spread along it
+#[derive(Default, Clone, Copy, Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct SplatSurface {
+    pub y: f32,
+    pub min_x: f32,
+    pub max_x: f32,
+}
+
+impl SplatSurface {
+    pub fn top_of(pos: Vec2, size: Vec2) -> Self {
+        Self {
+            y: pos.y + size.y / 2.,
+            min_x: pos.x - size.x / 2.,
+            max_x: pos.x + size.x / 2.,
+        }
+    }
+}
+
+// dirt goes down while janitors scrub the splat, it is gone at 0
 #[derive(Default, Component, Reflect)]
 #[reflect(Component)]
-pub struct Splat;
+pub struct Splat {
+    pub dirt: u32,
+    pub max_dirt: u32, // bigger splats have more dirt
+}
 
 #[derive(Default, Component, Reflect)]
 #[reflect(Component)]
@@ -37,10 +170,111 @@ pub struct NetworkStatsUi;
 pub struct Crosshair;
 
 // cleaned up after every round
+// janitor pickups are collected by walking into them, fortress pickups by hitting them with a cake
+#[derive(Clone, Copy, Component, Reflect, Debug, PartialEq, Eq)]
+#[reflect(Component)]
+pub enum Pickup {
+    SpeedBoost,
+    StunImmunity,
+    AreaClean,
+    TripleShot,
+    BigSplats,
+}
+
+impl Default for Pickup {
+    fn default() -> Self {
+        Self::SpeedBoost
+    }
+}
+
+impl Pickup {
+    pub const ALL: [Pickup; 5] = [
+        Pickup::SpeedBoost,
+        Pickup::StunImmunity,
+        Pickup::AreaClean,
+        Pickup::TripleShot,
+        Pickup::BigSplats,
+    ];
+
+    pub fn for_janitors(&self) -> bool {
+        !matches!(self, Pickup::TripleShot | Pickup::BigSplats)
+    }
+}
+
+/// A timed effect of a pickup, removed once its frames ran out
+pub trait PickupEffect {
+    fn frames_mut(&mut self) -> &mut usize;
+}
+
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct SpeedBoost {
+    pub frames: usize,
+}
+
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct StunImmunity {
+    pub frames: usize,
+}
+
+// the janitor cleans every splat around it, without scrubbing
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct AreaClean {
+    pub frames: usize,
+}
+
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct TripleShot {
+    pub frames: usize,
+}
+
+#[derive(Default, Component, Reflect)]
+#[reflect(Component)]
+pub struct BigSplats {
+    pub frames: usize,
+}
+
+impl PickupEffect for SpeedBoost {
+    fn frames_mut(&mut self) -> &mut usize {
+        &mut self.frames
+    }
+}
+
+impl PickupEffect for StunImmunity {
+    fn frames_mut(&mut self) -> &mut usize {
+        &mut self.frames
+    }
+}
+
+impl PickupEffect for AreaClean {
+    fn frames_mut(&mut self) -> &mut usize {
+        &mut self.frames
+    }
+}
+
+impl PickupEffect for TripleShot {
+    fn frames_mut(&mut self) -> &mut usize {
+        &mut self.frames
+    }
+}
+
+impl PickupEffect for BigSplats {
+    fn frames_mut(&mut self) -> &mut usize {
+        &mut self.frames
+    }
+}
+
 #[derive(Default, Component, Reflect)]
 #[reflect(Component)]
 pub struct RoundEntity;
 
+// not rolled back, the preview is redrawn every frame
+#[derive(Component)]
+pub struct TrajectoryDot;
+
 // cleaned up after the game
 #[derive(Component)]
 pub struct GameEntity;
@@ -50,6 +284,17 @@ pub struct GameEntity;
 pub struct AttackerControls {
     pub vertical: f32,
     pub horizontal: f32,
+    pub scrub: bool,
+    pub dash: bool,
+    pub block: bool,
+}
+
+// frames until the janitor can use an ability again
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct AttackerCooldowns {
+    pub dash: usize,
+    pub block: usize,
 }
 
 #[derive(Default, Reflect, Component)]
@@ -58,6 +303,10 @@ pub struct DefenderControls {
     pub vertical: f32,
     pub horizontal: f32,
     pub fire: bool,
+    pub roll: bool, // moves the fortress instead of the crosshair sideways
+    pub next_cake: bool,
+    pub prev_cake: bool,
+    pub last_input: u16, // to only cycle cakes once per button press
 }
 
 #[derive(Clone, Copy, Component, Reflect, Debug, PartialEq, Eq)]
@@ -83,26 +332,41 @@ pub enum AttackerState {
     Land(usize),
     Walk(usize),
     Hit(usize),
+    Bumped(usize),
+    Scrub(usize),
+    Swipe(usize),
+    Dash(usize),
+    Block(usize),
 }
 
 impl AttackerState {
     pub fn can_walk(&self) -> bool {
         match self {
-            AttackerState::Hit(..) => false,
+            AttackerState::Hit(..)
+            | AttackerState::Bumped(..)
+            | AttackerState::Scrub(..)
+            | AttackerState::Swipe(..)
+            | AttackerState::Dash(..)
+            | AttackerState::Block(..) => false,
             _ => true,
         }
     }
 
-    pub fn is_stunned(&self) -> bool {
+    // dashing and blocking can interrupt these
+    pub fn can_use_ability(&self) -> bool {
         match self {
-            AttackerState::Hit(..) => true,
-            _ => false,
+            AttackerState::Hit(..)
+            | AttackerState::Bumped(..)
+            | AttackerState::Swipe(..)
+            | AttackerState::Dash(..)
+            | AttackerState::Block(..) => false,
+            _ => true,
         }
     }
 
-    pub fn can_clean(&self) -> bool {
+    pub fn is_stunned(&self) -> bool {
         match self {
-            AttackerState::Idle(..) | AttackerState::Walk(..) | AttackerState::Land(..) => true,
+            AttackerState::Hit(..) => true,
             _ => false,
         }
     }
@@ -122,6 +386,11 @@ impl AttackerState {
             AttackerState::Land(f) => *f,
             AttackerState::Walk(f) => *f,
             AttackerState::Hit(f) => *f,
+            AttackerState::Bumped(f) => *f,
+            AttackerState::Scrub(f) => *f,
+            AttackerState::Swipe(f) => *f,
+            AttackerState::Dash(f) => *f,
+            AttackerState::Block(f) => *f,
         }
     }
 }
@@ -137,6 +406,7 @@ impl Default for AttackerState {
 #[reflect(Component)]
 pub enum DefenderState {
     Idle(usize),
+    Charge(usize),
     Fire(usize),
 }
 
@@ -147,9 +417,16 @@ impl Default for DefenderState {
 }
 
 impl DefenderState {
+    // power of a shot released after charging for that many frames
+    pub fn charge_power(frames: usize) -> f32 {
+        let charge = frames.min(CHARGE_FRAMES) as f32 / CHARGE_FRAMES as f32;
+        MIN_CHARGE_POWER + (MAX_CHARGE_POWER - MIN_CHARGE_POWER) * charge
+    }
+
     pub fn get_frame(&self) -> usize {
         match self {
             DefenderState::Idle(f) => *f,
+            DefenderState::Charge(f) => *f,
             DefenderState::Fire(f) => *f,
         }
     }
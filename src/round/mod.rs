@@ -1,3 +1,5 @@
+use bevy::prelude::Color;
+
 mod components;
 pub mod resources;
 mod rollback_systems;
@@ -9,11 +11,16 @@ pub mod prelude {
 }
 
 // inputs
-const INPUT_UP: u8 = 0b00001;
-const INPUT_DOWN: u8 = 0b00010;
-const INPUT_LEFT: u8 = 0b00100;
-const INPUT_RIGHT: u8 = 0b01000;
-const INPUT_ACT: u8 = 0b10000;
+const INPUT_UP: u16 = 0b00001;
+const INPUT_DOWN: u16 = 0b00010;
+const INPUT_LEFT: u16 = 0b00100;
+const INPUT_RIGHT: u16 = 0b01000;
+const INPUT_ACT: u16 = 0b10000;
+const INPUT_SURRENDER: u16 = 0b100000;
+const INPUT_REMATCH: u16 = 0b1000000;
+const INPUT_ALT1: u16 = 0b10000000;
+const INPUT_ALT2: u16 = 0b100000000;
+const INPUT_ROLL: u16 = 0b1000000000;
 
 // animation params
 const FRAMES_PER_SPRITE: usize = 10; // TODO: variable frame length per animation and per frame in animation
@@ -21,27 +28,234 @@ const FRAMES_PER_SPRITE: usize = 10; // TODO: variable frame length per animatio
 // physics param
 const ATTACKER_SIZE: f32 = 24.;
 const MAX_SPEED: f32 = 100.;
-pub const JUMP_HEIGHT: f32 = 2. * ATTACKER_SIZE;
-pub const JUMP_TIME_TO_PEAK: f32 = 1.;
+const JUMP_HEIGHT: f32 = 2. * ATTACKER_SIZE;
+const JUMP_TIME_TO_PEAK: f32 = 1.;
+// assuming janitor is 1.80 tall and 24 pixels tall
+const PIXELS_PER_METER: f32 = 24.0 / 1.8;
+// derived as suggested in: https://www.youtube.com/watch?v=hG9SzQxaCm8
+// for real-world gravity, we would use -9.81 * PIXELS_PER_METER
+pub const GRAVITY: f32 = (-2. * JUMP_HEIGHT) / JUMP_TIME_TO_PEAK * PIXELS_PER_METER;
 const DEFENDER_SIZE: f32 = 168.;
 const GROUND_LEVEL: f32 = -100.;
 const CAKE_SIZE: f32 = 16.;
 
+// soft body cake params
+const CAKE_PARTICLES_PER_SIDE: u32 = 3;
+const CAKE_COMPLIANCE: f32 = 0.002;
+const CAKE_SQUISH_FRAMES: usize = 8;
+const CLUSTER_SPREAD_SPEED: f32 = 60.; // sideways speed of the crumbs when a cluster cake splits
+const SIBLING_CAKE_GAP: f32 = 2.; // between cakes spawned together, so their bodies don't overlap
+const BOUNCE_FRAMES: usize = 5; // bouncy cakes touching the ground again sooner are still in the same bounce
+
 // controls
 const CROSSHAIR_SPEED: f32 = 3.;
+const FORTRESS_SPEED: f32 = 0.75; // the fortress rolls a lot slower than the crosshair moves
 const IDLE_THRESH: f32 = 0.01;
 const LAND_FRAMES: usize = 3;
 const STUN_FRAMES: usize = 60;
+const SURRENDER_HOLD_FRAMES: u32 = 60; // holding escape this long surrenders an online match
+const BUMP_FRAMES: usize = 20;
+const BUMP_SPEED: f32 = 150.;
+const BUMP_HOP: f32 = 60.;
+const BUMP_DAMPING: f32 = 0.9;
+
+// charged shots, holding fire builds up power
+const CHARGE_FRAMES: usize = 60; // to reach full power
+const MIN_CHARGE_POWER: f32 = 0.5; // a tap
+const MAX_CHARGE_POWER: f32 = 1.5;
+
+// trajectory preview, only shown to the fortress player
+const PREVIEW_DOTS: usize = 20;
+const PREVIEW_DOT_STEPS: usize = 4; // physics steps between two dots
+const PREVIEW_DOT_SIZE: f32 = 3.;
+const PREVIEW_COLOR: Color = Color::rgba(1., 1., 1., 0.6);
+
+// fortress ammo, so spamming cakes isn't the best strategy
+const MAX_AMMO: u32 = 5;
+const AMMO_REGEN_FRAMES: u32 = 90; // to bake one cake
+const FIRE_COOLDOWN: u32 = FRAMES_PER_SPRITE as u32 * 6; // from one shot to the next
+
+// janitor abilities
+const SWIPE_FRAMES: usize = 20;
+const SWIPE_REACH: f32 = 2. * ATTACKER_SIZE; // in front of the janitor
+const SWIPE_RATE: u32 = 2; // dirt removed per frame, scrubbing removes 1
+const DASH_FRAMES: usize = 10;
+const DASH_SPEED: f32 = 300.;
+const DASH_COOLDOWN: usize = 90;
+const BLOCK_FRAMES: usize = 60; // longest a janitor can hold a block
+const BLOCK_COOLDOWN: usize = 60;
+const BLOCK_DEFLECT_SPEED: f32 = 200.;
+
+// pickups, spawned at random during the round
+const PICKUP_INTERVAL: u32 = 480; // frames between two pickups
+const MAX_PICKUPS: usize = 2; // lying around at once
+const PICKUP_SIZE: f32 = 12.;
+const PICKUP_FRAMES: usize = 300; // how long an effect lasts
+const PICKUP_COLORS: [Color; 5] = [
+    Color::rgb(0.3, 1., 0.3), // speed boost
+    Color::rgb(1., 1., 0.3),  // stun immunity
+    Color::rgb(0.3, 1., 1.),  // area clean
+    Color::rgb(1., 0.3, 0.3), // triple shot
+    Color::rgb(1., 0.3, 1.),  // big splats
+];
+const SPEED_BOOST: f32 = 1.5;
+const AREA_CLEAN_RADIUS: f32 = 3. * ATTACKER_SIZE;
+const TRIPLE_SHOT_SPREAD: f32 = 40.; // sideways speed difference between the cakes
+const BIG_SPLAT_SCALE: f32 = 1.5;
 
-// round params
-const NUM_ROUNDS: u32 = 2;
+// collision layers
+const LAYER_JANITOR: u32 = 0b01;
+const LAYER_SPLAT: u32 = 0b10;
+
+// players, one of them is the fortress and the others are janitors
+const MAX_PLAYERS: usize = 4;
+// janitor sprite tints, so players can tell each other apart
+const PLAYER_COLORS: [Color; MAX_PLAYERS] = [
+    Color::WHITE,
+    Color::rgb(1., 0.6, 0.6),
+    Color::rgb(0.6, 0.8, 1.),
+    Color::rgb(0.6, 1., 0.6),
+];
+
+// default round params, can be changed in the match settings
 const INTERLUDE_LENGTH: u32 = 300;
 const ROUND_LENGTH: u32 = 1800;
+const SUDDEN_DEATH_LENGTH: u32 = 600;
 
-// fortress pos
-const DEF_X_POS: f32 = 250.;
+// level params
+const PLATFORM_COLOR: Color = Color::rgb(0.55, 0.4, 0.3);
+const ONE_WAY_PLATFORM_COLOR: Color = Color::rgba(0.55, 0.4, 0.3, 0.6);
+const CONVEYOR_COLOR: Color = Color::rgb(0.35, 0.35, 0.4);
+const FAN_COLOR: Color = Color::rgba(0.8, 0.9, 1., 0.15);
+const PUDDLE_COLOR: Color = Color::rgba(0.4, 0.6, 1., 0.7);
+const PUDDLE_GRIP: f32 = 0.05; // fraction of the way to the target speed per frame on a puddle
 
 // cake splat params
 const MIN_SPLAT: u32 = 1;
 const MAX_SPLAT: u32 = 5;
 const SPLAT_SPREAD: f32 = 20.;
+const SPLAT_OFFSET: f32 = 12.; // splats are drawn this far above the surface they landed on
+const MIN_SPLAT_SCALE: f32 = 0.75;
+const MAX_SPLAT_SCALE: f32 = 1.5;
+const SPLAT_DIRT: f32 = 60.; // frames of scrubbing for a splat of scale 1
+const SCRUB_REACH: f32 = ATTACKER_SIZE / 2.;
+const MIN_SPLAT_ALPHA: f32 = 0.3; // almost clean splats are drawn this transparent
+
+// cake types, indexed by CakeType
+pub struct CakeParams {
+    pub color: Color, // tint of the cake sprite
+    pub ammo: u32,    // cakes used up by one shot
+    pub size: f32,
+    pub mass: f32, // of the whole cake
+    pub restitution: f32,
+    pub flight_time: f32, // to the crosshair when aiming at a target, shorter is flatter
+    pub arc: f32,         // apex height relative to the crosshair when aiming at the apex
+    pub min_splat: u32,
+    pub max_splat: u32, // exclusive
+    pub splat_spread: f32,
+    pub splat_scale: f32,
+    pub stun_frames: usize,
+    pub bounces: u32,       // ground contacts before the cake splats
+    pub fragments: u32,     // cluster cakes split into this many crumbs
+    pub split_frame: usize, // after this many frames of flight
+}
+
+impl CakeParams {
+    // every particle of the soft body weighs the same
+    pub fn particle_mass(&self) -> f32 {
+        self.mass / (CAKE_PARTICLES_PER_SIDE * CAKE_PARTICLES_PER_SIDE) as f32
+    }
+}
+
+static CAKE_PARAMS: [CakeParams; 5] = [
+    // normal
+    CakeParams {
+        color: Color::WHITE,
+        ammo: 1,
+        size: CAKE_SIZE,
+        mass: 1.,
+        restitution: 0.,
+        flight_time: JUMP_TIME_TO_PEAK,
+        arc: 1.,
+        min_splat: MIN_SPLAT,
+        max_splat: MAX_SPLAT,
+        splat_spread: SPLAT_SPREAD,
+        splat_scale: 1.,
+        stun_frames: STUN_FRAMES,
+        bounces: 0,
+        fragments: 0,
+        split_frame: 0,
+    },
+    // heavy, a big and flat shot that stuns for longer
+    CakeParams {
+        color: Color::rgb(0.7, 0.5, 0.4),
+        ammo: 2,
+        size: 24.,
+        mass: 3.,
+        restitution: 0.,
+        flight_time: 0.7,
+        arc: 0.6,
+        min_splat: 3,
+        max_splat: 6,
+        splat_spread: 30.,
+        splat_scale: 1.3,
+        stun_frames: 2 * STUN_FRAMES,
+        bounces: 0,
+        fragments: 0,
+        split_frame: 0,
+    },
+    // cluster, splits into crumbs in mid-air
+    CakeParams {
+        color: Color::rgb(1., 0.7, 0.9),
+        ammo: 2,
+        size: CAKE_SIZE,
+        mass: 1.,
+        restitution: 0.,
+        flight_time: 1.2,
+        arc: 1.2,
+        min_splat: 1,
+        max_splat: 3,
+        splat_spread: SPLAT_SPREAD,
+        splat_scale: 1.,
+        stun_frames: STUN_FRAMES / 2,
+        bounces: 0,
+        fragments: 3,
+        split_frame: 40,
+    },
+    // bouncy, hops along the ground a few times before splatting
+    CakeParams {
+        color: Color::rgb(0.6, 0.9, 1.),
+        ammo: 1,
+        size: 12.,
+        mass: 0.7,
+        restitution: 0.9,
+        flight_time: JUMP_TIME_TO_PEAK,
+        arc: 1.,
+        min_splat: 1,
+        max_splat: 3,
+        splat_spread: 15.,
+        splat_scale: 0.8,
+        stun_frames: STUN_FRAMES / 2,
+        bounces: 3,
+        fragments: 0,
+        split_frame: 0,
+    },
+    // crumb, what cluster cakes split into
+    CakeParams {
+        color: Color::rgb(1., 0.7, 0.9),
+        ammo: 0,
+        size: 8.,
+        mass: 0.3,
+        restitution: 0.,
+        flight_time: JUMP_TIME_TO_PEAK,
+        arc: 1.,
+        min_splat: 1,
+        max_splat: 2,
+        splat_spread: 10.,
+        splat_scale: 0.6,
+        stun_frames: STUN_FRAMES / 2,
+        bounces: 0,
+        fragments: 0,
+        split_frame: 0,
+    },
+];
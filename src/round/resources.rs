@@ -1,10 +1,21 @@
+use std::ops::Range;
+
 use bevy::{prelude::*, utils::HashMap};
 use bytemuck::{Pod, Zeroable};
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::NUM_PLAYERS;
+
+use super::{
+    AMMO_REGEN_FRAMES, FIRE_COOLDOWN, INTERLUDE_LENGTH, MAX_AMMO, MAX_PLAYERS, ROUND_LENGTH,
+    SUDDEN_DEATH_LENGTH,
+};
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
 pub struct Input {
-    pub inp: u8,
+    pub inp: u16,
 }
 
 #[derive(Default, Reflect, Hash, Component)]
@@ -13,6 +24,62 @@ pub struct FrameCount {
     pub frame: u32,
 }
 
+/// All gameplay randomness comes from this one stream. Only the seed and the position in the
+/// stream are stored, so the generator is saved and restored with the rollback snapshot.
+#[derive(Debug, Default, Clone, Copy, Reflect, Hash, Component)]
+#[reflect(Hash)]
+pub struct MatchRng {
+    pub seed: u64,
+    pub word_pos: u64,
+}
+
+impl MatchRng {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, word_pos: 0 }
+    }
+
+    /// Every peer ends up with the same seed, no matter in which order the others joined
+    pub fn from_peers(mut peer_ids: Vec<String>) -> Self {
+        peer_ids.sort();
+        // FNV-1a, the ids are separated by a zero byte, so they can't run into each other
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in peer_ids
+            .iter()
+            .flat_map(|id| id.bytes().chain(std::iter::once(0)))
+        {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        Self::new(hash)
+    }
+
+    fn with_rng<T>(&mut self, f: impl FnOnce(&mut ChaCha8Rng) -> T) -> T {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        rng.set_word_pos(self.word_pos as u128);
+        let result = f(&mut rng);
+        self.word_pos = rng.get_word_pos() as u64;
+        result
+    }
+}
+
+impl RngCore for MatchRng {
+    fn next_u32(&mut self) -> u32 {
+        self.with_rng(|rng| rng.next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.with_rng(|rng| rng.next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.with_rng(|rng| rng.fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.with_rng(|rng| rng.try_fill_bytes(dest))
+    }
+}
+
 #[derive(Copy, Clone, Reflect, Hash, Component)]
 #[reflect(Hash)]
 pub enum RoundState {
@@ -22,6 +89,41 @@ pub enum RoundState {
     RoundStart,
     Round,
     RoundEnd,
+    MatchEnd, // the win screen is shown, players can ask for a rematch
+}
+
+// how janitors interact with each other, chosen before the match
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum JanitorInteraction {
+    PassThrough, // janitors do not collide
+    Bump,        // janitors knock each other back when running into each other
+    Stack,       // janitors are solid and can stand on each other's heads
+}
+
+impl JanitorInteraction {
+    pub fn next(&self) -> Self {
+        match self {
+            JanitorInteraction::PassThrough => JanitorInteraction::Bump,
+            JanitorInteraction::Bump => JanitorInteraction::Stack,
+            JanitorInteraction::Stack => JanitorInteraction::PassThrough,
+        }
+    }
+}
+
+impl Default for JanitorInteraction {
+    fn default() -> Self {
+        Self::Stack
+    }
+}
+
+impl std::fmt::Display for JanitorInteraction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JanitorInteraction::PassThrough => write!(f, "Pass Through"),
+            JanitorInteraction::Bump => write!(f, "Bump"),
+            JanitorInteraction::Stack => write!(f, "Stack"),
+        }
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -49,28 +151,446 @@ pub struct ConnectionInfo {
     pub ping: u128,
 }
 
+// what happens if several players share the best score at the end of the match
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TieBreak {
+    SuddenDeath, // one more leg with shorter rounds, only the tied players can win it
+    Draw,
+}
+
+impl Default for TieBreak {
+    fn default() -> Self {
+        TieBreak::SuddenDeath
+    }
+}
+
+impl TieBreak {
+    pub fn next(&self) -> Self {
+        match self {
+            TieBreak::SuddenDeath => TieBreak::Draw,
+            TieBreak::Draw => TieBreak::SuddenDeath,
+        }
+    }
+}
+
+impl std::fmt::Display for TieBreak {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TieBreak::SuddenDeath => write!(f, "Sudden Death"),
+            TieBreak::Draw => write!(f, "Draw"),
+        }
+    }
+}
+
+// what the crosshair marks for the fortress
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AimMode {
+    Apex,   // the highest point of the arc
+    Target, // where the cake passes through
+}
+
+impl Default for AimMode {
+    fn default() -> Self {
+        AimMode::Apex
+    }
+}
+
+impl AimMode {
+    pub fn next(&self) -> Self {
+        match self {
+            AimMode::Apex => AimMode::Target,
+            AimMode::Target => AimMode::Apex,
+        }
+    }
+}
+
+impl std::fmt::Display for AimMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AimMode::Apex => write!(f, "Apex"),
+            AimMode::Target => write!(f, "Target"),
+        }
+    }
+}
+
+// settings both players agree on before the match starts, they do not change during the match
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MatchSettings {
+    pub num_players: usize,
+    pub round_length: u32,     // in frames
+    pub interlude_length: u32, // in frames
+    pub best_of: u32, // number of legs, the match ends as soon as a player won the majority
+    pub janitor_interaction: JanitorInteraction,
+    pub team_mode: bool, // janitors share a score against the fortress instead of competing
+    pub tie_break: TieBreak,
+    pub aim_mode: AimMode,
+}
+
+impl Default for MatchSettings {
+    fn default() -> Self {
+        Self {
+            num_players: NUM_PLAYERS,
+            round_length: ROUND_LENGTH,
+            interlude_length: INTERLUDE_LENGTH,
+            best_of: 1,
+            janitor_interaction: JanitorInteraction::default(),
+            team_mode: false,
+            tie_break: TieBreak::default(),
+            aim_mode: AimMode::default(),
+        }
+    }
+}
+
+impl MatchSettings {
+    // rounds per leg, every player defends once per leg
+    pub fn num_rounds(&self) -> u32 {
+        self.num_players as u32
+    }
+
+    pub fn next_num_players(&self) -> usize {
+        if self.num_players >= MAX_PLAYERS {
+            2
+        } else {
+            self.num_players + 1
+        }
+    }
+
+    pub fn next_best_of(&self) -> u32 {
+        match self.best_of {
+            1 => 3,
+            3 => 5,
+            _ => 1,
+        }
+    }
+
+    pub fn next_round_length(&self) -> u32 {
+        match self.round_length {
+            1200 => 1800,
+            1800 => 3600,
+            _ => 1200,
+        }
+    }
+
+    /// Identifies the settings, so only players with the same settings get matched with each other
+    pub fn room_tag(&self) -> String {
+        format!(
+            "p{}l{}i{}b{}{:?}{}{:?}{:?}",
+            self.num_players,
+            self.round_length,
+            self.interlude_length,
+            self.best_of,
+            self.janitor_interaction,
+            if self.team_mode { "T" } else { "" },
+            self.tie_break,
+            self.aim_mode
+        )
+    }
+}
+
 #[derive(Debug, Default, Clone, Reflect, Component)]
 pub struct RoundData {
-    pub cur_round: u32,               // the current round
-    pub results: HashMap<u32, usize>, // key: round, value: remaining splats
+    pub cur_round: u32,                               // the current round
+    pub results: HashMap<u32, usize>,                 // key: round, value: remaining splats
+    pub cleaned: HashMap<u32, HashMap<usize, usize>>, // key: round, value: splats cleaned per janitor
+    pub sudden_death: bool,                           // the regular rounds ended in a tie
+    pub regular_rounds: u32,                          // rounds played before sudden death
+    pub first_defender: usize,                        // changes with every rematch
 }
 
 impl RoundData {
-    pub fn to_string(&self) -> String {
+    // roles rotate every round, so every player gets to defend once per leg
+    pub fn defender_of(&self, round: u32, settings: &MatchSettings) -> usize {
+        (round as usize + self.first_defender) % settings.num_players
+    }
+
+    pub fn defender(&self, settings: &MatchSettings) -> usize {
+        self.defender_of(self.cur_round, settings)
+    }
+
+    pub fn round_length(&self, settings: &MatchSettings) -> u32 {
+        if self.sudden_death {
+            SUDDEN_DEATH_LENGTH.min(settings.round_length)
+        } else {
+            settings.round_length
+        }
+    }
+
+    pub fn start_sudden_death(&mut self) {
+        self.sudden_death = true;
+        self.regular_rounds = self.cur_round;
+    }
+
+    fn regular_rounds(&self) -> u32 {
+        if self.sudden_death {
+            self.regular_rounds
+        } else {
+            self.cur_round
+        }
+    }
+
+    pub fn summary(&self, settings: &MatchSettings) -> String {
         let mut str = String::new();
-        for (k, v) in self.results.iter() {
-            str.push_str(&format!("Janitor {}: {} splats left\n", k + 1, v));
+        if settings.team_mode {
+            // every round is one fortress against the team of everybody else
+            for round in 0..self.cur_round {
+                if let Some(splats) = self.results.get(&round) {
+                    let defender = self.defender_of(round, settings);
+                    let team: Vec<String> = (0..settings.num_players)
+                        .filter(|handle| *handle != defender)
+                        .map(|handle| (handle + 1).to_string())
+                        .collect();
+                    str.push_str(&format!(
+                        "Round {}: Fortress {} vs Team {}: {} splats left\n",
+                        round + 1,
+                        defender + 1,
+                        team.join("+"),
+                        splats
+                    ));
+                }
+            }
+        }
+        let scores = self.scores(0..self.cur_round, settings);
+        let cleaned = self.splats_cleaned(0..self.cur_round);
+        let legs_won = self.legs_won(settings);
+        for handle in 0..settings.num_players {
+            let splats = Self::score(&scores, handle);
+            if settings.team_mode {
+                str.push_str(&format!("Fortress {}: {} splats total", handle + 1, splats));
+            } else {
+                str.push_str(&format!(
+                    "Player {}: {} points, {} cleaned",
+                    handle + 1,
+                    splats,
+                    Self::score(&cleaned, handle)
+                ));
+            }
+            if settings.best_of > 1 {
+                let legs = legs_won.get(&handle).copied().unwrap_or(0);
+                str.push_str(&format!(", {} legs won", legs));
+            }
+            str.push('\n');
         }
-        //str.push_str(&format!("\nJanitor {} wins!", winner + 1));
         str
     }
 
-    pub fn winner(&self) -> u32 {
-        self.results
-            .iter()
-            .min_by(|a, b| a.1.cmp(&b.1))
-            .map(|(k, _)| *k)
-            .expect("No entries in results.")
+    /// Sums up the splats left after the rounds a player spent as the fortress, key: player handle
+    pub fn fortress_splats(
+        &self,
+        rounds: Range<u32>,
+        settings: &MatchSettings,
+    ) -> HashMap<usize, usize> {
+        let mut fortress_splats = HashMap::default();
+        for round in rounds {
+            if let Some(splats) = self.results.get(&round) {
+                *fortress_splats
+                    .entry(self.defender_of(round, settings))
+                    .or_insert(0) += splats;
+            }
+        }
+        fortress_splats
+    }
+
+    // the janitor that finished the splat gets the credit, see splat_cleaning
+    pub fn add_cleaned(&mut self, handle: usize) {
+        *self
+            .cleaned
+            .entry(self.cur_round)
+            .or_default()
+            .entry(handle)
+            .or_insert(0) += 1;
+    }
+
+    /// Sums up the splats each janitor cleaned, key: player handle
+    pub fn splats_cleaned(&self, rounds: Range<u32>) -> HashMap<usize, usize> {
+        let mut splats_cleaned = HashMap::default();
+        for round in rounds {
+            for (handle, splats) in self.cleaned.get(&round).into_iter().flatten() {
+                *splats_cleaned.entry(*handle).or_insert(0) += splats;
+            }
+        }
+        splats_cleaned
+    }
+
+    // higher is better. In team mode, the janitors share the result of their round, so players
+    // are ranked by the mess they made as the fortress. Otherwise, every splat a player cleaned
+    // as a janitor counts as well.
+    fn scores(&self, rounds: Range<u32>, settings: &MatchSettings) -> HashMap<usize, usize> {
+        let mut scores = self.fortress_splats(rounds.clone(), settings);
+        if !settings.team_mode {
+            for (handle, splats) in self.splats_cleaned(rounds) {
+                *scores.entry(handle).or_insert(0) += splats;
+            }
+        }
+        scores
+    }
+
+    fn score(scores: &HashMap<usize, usize>, handle: usize) -> usize {
+        scores.get(&handle).copied().unwrap_or(0)
+    }
+
+    // the best score wins the leg, ties go to the lower handle
+    fn leg_winner(&self, leg: u32, settings: &MatchSettings) -> Option<usize> {
+        let start = leg * settings.num_rounds();
+        let scores = self.scores(start..start + settings.num_rounds(), settings);
+        (0..settings.num_players)
+            .max_by_key(|handle| (Self::score(&scores, *handle), std::cmp::Reverse(*handle)))
+    }
+
+    // key: player handle, value: legs won
+    pub fn legs_won(&self, settings: &MatchSettings) -> HashMap<usize, u32> {
+        let mut legs_won = HashMap::default();
+        for leg in 0..self.regular_rounds() / settings.num_rounds() {
+            if let Some(winner) = self.leg_winner(leg, settings) {
+                *legs_won.entry(winner).or_insert(0) += 1;
+            }
+        }
+        legs_won
+    }
+
+    pub fn match_over(&self, settings: &MatchSettings) -> bool {
+        if self.sudden_death {
+            return self.cur_round >= self.regular_rounds + settings.num_rounds();
+        }
+        let legs_to_win = settings.best_of / 2 + 1;
+        self.cur_round >= settings.num_rounds() * settings.best_of
+            || self
+                .legs_won(settings)
+                .values()
+                .any(|legs| *legs >= legs_to_win)
+    }
+
+    // most legs won, then best score overall. Several winners mean a tie.
+    pub fn winners(&self, settings: &MatchSettings) -> Vec<usize> {
+        let players: Vec<usize> = (0..settings.num_players).collect();
+        self.winners_among(&players, settings)
+    }
+
+    /// Like winners, but only the given players are considered
+    pub fn winners_among(&self, players: &[usize], settings: &MatchSettings) -> Vec<usize> {
+        let legs_won = self.legs_won(settings);
+        let scores = self.scores(0..self.regular_rounds(), settings);
+        let key = |handle: &usize| {
+            (
+                legs_won.get(handle).copied().unwrap_or(0),
+                Self::score(&scores, *handle),
+            )
+        };
+        let winners = Self::best(players.iter().copied(), key);
+        if !self.sudden_death {
+            return winners;
+        }
+
+        // only the players that tied in the regular rounds can win sudden death
+        let scores = self.scores(self.regular_rounds..self.cur_round, settings);
+        Self::best(winners.into_iter(), |handle| Self::score(&scores, *handle))
+    }
+
+    // all handles sharing the highest key, in ascending order
+    fn best<K: Ord>(handles: impl Iterator<Item = usize>, key: impl Fn(&usize) -> K) -> Vec<usize> {
+        let mut best: Vec<usize> = Vec::new();
+        let mut best_key = None;
+        for handle in handles {
+            let k = key(&handle);
+            match best_key.as_ref().map(|b| k.cmp(b)) {
+                Some(std::cmp::Ordering::Less) => {}
+                Some(std::cmp::Ordering::Equal) => best.push(handle),
+                _ => {
+                    best = vec![handle];
+                    best_key = Some(k);
+                }
+            }
+        }
+        best
+    }
+}
+
+/// Cakes the fortress has left, it bakes new ones over time
+#[derive(Debug, Clone, Copy, Reflect, Hash, Component)]
+#[reflect(Hash)]
+pub struct DefenderAmmo {
+    pub cakes: u32,
+    pub regen: u32,    // frames spent baking the next cake
+    pub cooldown: u32, // frames until the fortress can fire again
+}
+
+impl Default for DefenderAmmo {
+    fn default() -> Self {
+        Self {
+            cakes: MAX_AMMO,
+            regen: 0,
+            cooldown: 0,
+        }
+    }
+}
+
+impl DefenderAmmo {
+    pub fn can_fire(&self, cost: u32) -> bool {
+        self.cooldown == 0 && self.cakes >= cost
+    }
+
+    pub fn fire(&mut self, cost: u32) {
+        self.cakes -= cost;
+        self.cooldown = FIRE_COOLDOWN;
+    }
+
+    pub fn update(&mut self) {
+        self.cooldown = self.cooldown.saturating_sub(1);
+        if self.cakes >= MAX_AMMO {
+            self.regen = 0;
+            return;
+        }
+        self.regen += 1;
+        if self.regen >= AMMO_REGEN_FRAMES {
+            self.cakes += 1;
+            self.regen = 0;
+        }
+    }
+}
+
+/// Statistics over the whole match, key: player handle
+#[derive(Debug, Default, Clone, Reflect, Component)]
+pub struct MatchStats {
+    pub cakes_fired: HashMap<usize, u32>,     // as the fortress
+    pub cakes_on_target: HashMap<usize, u32>, // as the fortress, cakes that hit at least one janitor
+    pub splats_spawned: HashMap<usize, u32>,  // as the fortress
+    pub splats_cleaned: HashMap<usize, u32>,  // as a janitor
+    pub cake_hits: HashMap<usize, u32>,       // as a janitor, how often they got hit by a cake
+    pub stun_frames: HashMap<usize, u32>,     // as a janitor
+}
+
+impl MatchStats {
+    pub fn add(stat: &mut HashMap<usize, u32>, handle: usize, amount: u32) {
+        *stat.entry(handle).or_insert(0) += amount;
+    }
+
+    fn get(stat: &HashMap<usize, u32>, handle: usize) -> u32 {
+        stat.get(&handle).copied().unwrap_or(0)
+    }
+
+    /// Percentage of fired cakes that hit a janitor, every crumb of a cluster cake counts as fired
+    pub fn accuracy(&self, handle: usize) -> u32 {
+        let fired = Self::get(&self.cakes_fired, handle);
+        if fired == 0 {
+            return 0;
+        }
+        Self::get(&self.cakes_on_target, handle) * 100 / fired
+    }
+
+    pub fn breakdown(&self, settings: &MatchSettings) -> String {
+        let mut str = String::new();
+        for handle in 0..settings.num_players {
+            str.push_str(&format!(
+                "Player {}: Fortress {} cakes, {}% on target, {} splats | Janitor {} cleaned, {} hits, {}s stunned\n",
+                handle + 1,
+                Self::get(&self.cakes_fired, handle),
+                self.accuracy(handle),
+                Self::get(&self.splats_spawned, handle),
+                Self::get(&self.splats_cleaned, handle),
+                Self::get(&self.cake_hits, handle),
+                Self::get(&self.stun_frames, handle) / 60,
+            ));
+        }
+        str
     }
 }
 
@@ -79,3 +599,60 @@ impl Default for RoundState {
         Self::InterludeStart
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(team_mode: bool) -> MatchSettings {
+        MatchSettings {
+            num_players: 3,
+            team_mode,
+            ..Default::default()
+        }
+    }
+
+    // one leg, every player defends once, in order of their handles
+    fn played_leg() -> RoundData {
+        let mut round_data = RoundData::default();
+        let rounds: [(usize, &[usize]); 3] = [
+            (5, &[1, 1, 1, 1]), // player 1 cleans up after fortress 0
+            (4, &[2]),          // player 2 barely helps against fortress 1
+            (3, &[1, 1]),
+        ];
+        for (splats_left, cleaned_by) in rounds {
+            for handle in cleaned_by {
+                round_data.add_cleaned(*handle);
+            }
+            round_data.results.insert(round_data.cur_round, splats_left);
+            round_data.cur_round += 1;
+        }
+        round_data
+    }
+
+    #[test]
+    fn team_mode_ranks_by_fortress_mess() {
+        assert_eq!(played_leg().winners(&settings(true)), vec![0]);
+    }
+
+    #[test]
+    fn free_for_all_ranks_janitors_individually() {
+        // 0: 5 left as fortress, 1: 4 left + 6 cleaned, 2: 3 left + 1 cleaned
+        assert_eq!(played_leg().winners(&settings(false)), vec![1]);
+    }
+
+    #[test]
+    fn janitors_of_the_same_round_score_differently() {
+        let mut round_data = RoundData::default();
+        round_data.add_cleaned(1);
+        round_data.add_cleaned(1);
+        round_data.add_cleaned(2);
+        round_data.results.insert(0, 2);
+        round_data.cur_round = 1;
+
+        let scores = round_data.scores(0..1, &settings(false));
+        assert_ne!(scores.get(&1), scores.get(&2));
+        let scores = round_data.scores(0..1, &settings(true));
+        assert_eq!(scores.get(&1), scores.get(&2));
+    }
+}
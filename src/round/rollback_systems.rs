@@ -1,22 +1,42 @@
-use bevy::prelude::*;
+use bevy::{ecs::system::EntityCommands, prelude::*};
 use bevy_ggrs::{Rollback, RollbackIdProvider};
 use ggrs::InputStatus;
-use rand::{Rng, SeedableRng};
+use physics::{
+    ballistics,
+    components::{
+        CircleCollider, CollisionLayers, DistanceConstraint, ForceZone, Mass, OneWay, Restitution,
+        SoftBodyParticle,
+    },
+    prelude::*,
+    DELTA_TIME,
+};
+use rand::Rng;
+use std::f32::consts::SQRT_2;
 
 use crate::{
     checksum::Checksum,
-    menu::{connect::LocalHandles, win::MatchResult},
-    physics::prelude::*,
+    level::{Floor, HazardKind, Level, LevelAssets, LevelBox, LevelHazard},
+    menu::{
+        connect::LocalHandles,
+        win::{MatchResult, Rematch},
+    },
     round::{prelude::*, resources::Input},
-    AppState, AttackerAssets, DefenderAssets, FontAssets, MiscAssets, BUTTON_TEXT, NUM_PLAYERS,
-    SCREEN_X, SCREEN_Y,
+    AppState, AttackerAssets, DefenderAssets, FontAssets, MiscAssets, BUTTON_TEXT, SCREEN_X,
+    SCREEN_Y,
 };
 
 use super::{
-    ATTACKER_SIZE, CAKE_SIZE, CROSSHAIR_SPEED, DEFENDER_SIZE, DEF_X_POS, FRAMES_PER_SPRITE,
-    GROUND_LEVEL, IDLE_THRESH, INPUT_ACT, INPUT_DOWN, INPUT_LEFT, INPUT_RIGHT, INPUT_UP,
-    INTERLUDE_LENGTH, JUMP_HEIGHT, JUMP_TIME_TO_PEAK, LAND_FRAMES, MAX_SPEED, MAX_SPLAT, MIN_SPLAT,
-    NUM_ROUNDS, ROUND_LENGTH, SPLAT_SPREAD, STUN_FRAMES,
+    AREA_CLEAN_RADIUS, ATTACKER_SIZE, BIG_SPLAT_SCALE, BLOCK_COOLDOWN, BLOCK_DEFLECT_SPEED,
+    BLOCK_FRAMES, BOUNCE_FRAMES, BUMP_DAMPING, BUMP_FRAMES, BUMP_HOP, BUMP_SPEED, CAKE_COMPLIANCE,
+    CAKE_PARTICLES_PER_SIDE, CAKE_SQUISH_FRAMES, CLUSTER_SPREAD_SPEED, CONVEYOR_COLOR,
+    CROSSHAIR_SPEED, DASH_COOLDOWN, DASH_FRAMES, DASH_SPEED, DEFENDER_SIZE, FAN_COLOR,
+    FORTRESS_SPEED, FRAMES_PER_SPRITE, GROUND_LEVEL, IDLE_THRESH, INPUT_ACT, INPUT_ALT1,
+    INPUT_ALT2, INPUT_DOWN, INPUT_LEFT, INPUT_REMATCH, INPUT_RIGHT, INPUT_ROLL, INPUT_SURRENDER,
+    INPUT_UP, JUMP_HEIGHT, JUMP_TIME_TO_PEAK, LAND_FRAMES, LAYER_JANITOR, LAYER_SPLAT, MAX_PICKUPS,
+    MAX_SPEED, MAX_SPLAT_SCALE, MIN_SPLAT_SCALE, ONE_WAY_PLATFORM_COLOR, PICKUP_COLORS,
+    PICKUP_FRAMES, PICKUP_INTERVAL, PICKUP_SIZE, PLATFORM_COLOR, PLAYER_COLORS, PUDDLE_COLOR,
+    PUDDLE_GRIP, SCRUB_REACH, SIBLING_CAKE_GAP, SPEED_BOOST, SPLAT_DIRT, SPLAT_OFFSET,
+    SWIPE_FRAMES, SWIPE_RATE, SWIPE_REACH, TRIPLE_SHOT_SPREAD,
 };
 
 /*
@@ -28,22 +48,52 @@ pub fn setup_interlude(
     mut state: ResMut<RoundState>,
     round_data: Res<RoundData>,
     local_handles: Res<LocalHandles>,
+    settings: Res<MatchSettings>,
     font_assets: Res<FontAssets>,
 ) {
+    let defender = round_data.defender(&settings);
     // local mode
     let info_string = if local_handles.handles.len() > 1 {
-        if round_data.cur_round == 0 {
-            "Janitor: ARROWS\n Clean the cake splats!\nFortress WASD + SPACE\n Make a MESS!"
-                .to_owned()
+        // local handles are the player handles, so they also index the keymaps
+        let janitor_keys: Vec<String> = (0..settings.num_players)
+            .filter(|handle| *handle != defender)
+            .map(|handle| {
+                let keys = &KEYMAPS[handle];
+                format!("{} + {}", keys.move_name, keys.act_name)
+            })
+            .collect();
+        let round_switch = if round_data.cur_round == 0 {
+            ""
+        } else if round_data.sudden_death && round_data.cur_round == round_data.regular_rounds {
+            "SUDDEN DEATH!\n"
         } else {
-            "ROUND SWITCH!\nJanitor: WASD\n Clean the cake splats!\nFortress ARROWS + RSHIFT\n Make a MESS!"
-                .to_owned()
-        }
+            "ROUND SWITCH!\n"
+        };
+        format!(
+            "{}Janitors: {}\n Action + UP dashes, + DOWN blocks\n Scrub the cake splats!\nFortress {} + {}/{}, {} rolls\n Make a MESS!",
+            round_switch,
+            janitor_keys.join(", "),
+            KEYMAPS[defender].move_name,
+            KEYMAPS[defender].act_name,
+            KEYMAPS[defender].alt_name,
+            KEYMAPS[defender].roll_name
+        )
     } else {
-        if round_data.cur_round as usize == local_handles.handles[0] {
-            "You are the Fortress!\nWASD + SPACE\n Make a MESS!".to_owned()
+        let sudden_death = if round_data.sudden_death {
+            "SUDDEN DEATH!\n"
+        } else {
+            ""
+        };
+        if defender == local_handles.handles[0] {
+            format!(
+                "{}You are the Fortress!\nWASD + SPACE/Q/E, LSHIFT rolls\n Make a MESS!",
+                sudden_death
+            )
         } else {
-            "You are the Janitor!\nWASD\n Clean the cake splats!".to_owned()
+            format!(
+                "{}You are a Janitor!\nWASD + SPACE, SPACE + W dashes, SPACE + S blocks\n Scrub the cake splats!",
+                sudden_death
+            )
         }
     };
     *state = RoundState::Interlude;
@@ -67,9 +117,13 @@ pub fn setup_interlude(
         .insert(Interlude);
 }
 
-pub fn run_interlude(mut frame_count: ResMut<FrameCount>, mut state: ResMut<RoundState>) {
+pub fn run_interlude(
+    mut frame_count: ResMut<FrameCount>,
+    mut state: ResMut<RoundState>,
+    settings: Res<MatchSettings>,
+) {
     frame_count.frame += 1;
-    if frame_count.frame >= INTERLUDE_LENGTH {
+    if frame_count.frame >= settings.interlude_length {
         *state = RoundState::InterludeEnd;
     }
 }
@@ -96,56 +150,100 @@ pub fn spawn_world(
     mut commands: Commands,
     mut rip: ResMut<RollbackIdProvider>,
     font_assets: Res<FontAssets>,
+    settings: Res<MatchSettings>,
+    round_data: Res<RoundData>,
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<Level>>,
 ) {
-    // todo: could import the body builder from bevy_xpbd to clean this up
-    let ground_size = Vec2::new(2000., 2000.); // should just be bigger than the screen
-
-    // ground
-    commands
-        .spawn_bundle(StaticBoxBundle {
-            pos: Pos(Vec2::new(0., -ground_size.y / 2. + GROUND_LEVEL)),
-            collider: BoxCollider { size: ground_size },
-            ..Default::default()
-        })
-        .insert(Rollback::new(rip.next_id()))
-        .insert(RoundEntity);
+    let level = levels
+        .get(&level_assets.default_level)
+        .expect("Level not loaded.");
 
-    // left
-    commands
-        .spawn_bundle(StaticBoxBundle {
-            pos: Pos(Vec2::new(-SCREEN_X / 4. - ground_size.y / 2., 0.)),
-            collider: BoxCollider { size: ground_size },
-            ..Default::default()
-        })
-        .insert(Rollback::new(rip.next_id()))
-        .insert(RoundEntity);
+    // todo: could import the body builder from bevy_xpbd to clean this up
+    for LevelBox { pos, size, .. } in level.statics.iter() {
+        commands
+            .spawn_bundle(StaticBoxBundle {
+                pos: Pos(*pos),
+                collider: BoxCollider { size: *size },
+                ..Default::default()
+            })
+            .insert(Rollback::new(rip.next_id()))
+            .insert(RoundEntity);
+    }
 
-    // right
-    commands
-        .spawn_bundle(StaticBoxBundle {
-            pos: Pos(Vec2::new(SCREEN_X / 4. + ground_size.y / 2., 0.)),
-            collider: BoxCollider { size: ground_size },
+    for LevelHazard { pos, size, kind } in level.hazards.iter() {
+        let color = match kind {
+            HazardKind::Conveyor(..) => CONVEYOR_COLOR,
+            HazardKind::Fan(..) => FAN_COLOR,
+            HazardKind::Puddle => PUDDLE_COLOR,
+        };
+        let mut hazard = commands.spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color,
+                custom_size: Some(*size),
+                ..Default::default()
+            },
+            transform: Transform::from_xyz(pos.x, pos.y, 4.),
             ..Default::default()
-        })
-        .insert(Rollback::new(rip.next_id()))
-        .insert(RoundEntity);
+        });
+        hazard
+            .insert(Rollback::new(rip.next_id()))
+            .insert(RoundEntity);
+        match *kind {
+            HazardKind::Conveyor(speed) => {
+                hazard
+                    .insert_bundle(StaticBoxBundle {
+                        pos: Pos(*pos),
+                        collider: BoxCollider { size: *size },
+                        ..Default::default()
+                    })
+                    .insert(Conveyor { speed });
+            }
+            HazardKind::Fan(force) => {
+                hazard
+                    .insert(Pos(*pos))
+                    .insert(ForceZone { size: *size, force });
+            }
+            HazardKind::Puddle => {
+                hazard.insert(Puddle { size: *size });
+            }
+        }
+    }
 
-    // up
-    commands
-        .spawn_bundle(StaticBoxBundle {
-            pos: Pos(Vec2::new(0., SCREEN_Y / 4. + ground_size.y / 2.)),
-            collider: BoxCollider { size: ground_size },
+    for LevelBox { pos, size, one_way } in level.platforms.iter() {
+        let mut platform = commands.spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: if *one_way {
+                    ONE_WAY_PLATFORM_COLOR
+                } else {
+                    PLATFORM_COLOR
+                },
+                custom_size: Some(*size),
+                ..Default::default()
+            },
+            transform: Transform::from_xyz(pos.x, pos.y, 5.),
             ..Default::default()
-        })
-        .insert(Rollback::new(rip.next_id()))
-        .insert(RoundEntity);
+        });
+        platform
+            .insert_bundle(StaticBoxBundle {
+                pos: Pos(*pos),
+                collider: BoxCollider { size: *size },
+                ..Default::default()
+            })
+            .insert(Platform)
+            .insert(Rollback::new(rip.next_id()))
+            .insert(RoundEntity);
+        if *one_way {
+            platform.insert(OneWay);
+        }
+    }
 
     // screen timer
     commands
         .spawn_bundle(Text2dBundle {
             transform: Transform::from_xyz(0., -SCREEN_Y / 4. - GROUND_LEVEL / 2., 100.),
             text: Text::with_section(
-                (ROUND_LENGTH / 60).to_string(),
+                (round_data.round_length(&settings) / 60).to_string(),
                 TextStyle {
                     font: font_assets.default_font.clone(),
                     font_size: 40.0,
@@ -168,18 +266,33 @@ pub fn spawn_attackers(
     mut rip: ResMut<RollbackIdProvider>,
     sprites: Res<AttackerAssets>,
     round_data: Res<RoundData>,
+    settings: Res<MatchSettings>,
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<Level>>,
 ) {
-    for handle in 0..NUM_PLAYERS {
+    let level = levels
+        .get(&level_assets.default_level)
+        .expect("Level not loaded.");
+    let layers = match settings.janitor_interaction {
+        JanitorInteraction::PassThrough => CollisionLayers::new(LAYER_JANITOR, !LAYER_JANITOR),
+        _ => CollisionLayers::default(),
+    };
+
+    let mut spawned = 0;
+    for handle in 0..settings.num_players {
         // this player will be the defender instead
-        if handle == round_data.cur_round as usize {
+        if handle == round_data.defender(&settings) {
             continue;
         }
-        let x = 0.;
-        let y = 0.;
+        let Vec2 { x, y } = level.janitor_spawn(spawned);
+        spawned += 1;
         commands
             .spawn_bundle(SpriteSheetBundle {
                 transform: Transform::from_xyz(x, y, (handle + 2) as f32),
-                sprite: TextureAtlasSprite::new(0),
+                sprite: TextureAtlasSprite {
+                    color: PLAYER_COLORS[handle],
+                    ..TextureAtlasSprite::new(0)
+                },
                 texture_atlas: sprites.janitor_idle.clone(),
                 ..Default::default()
             })
@@ -190,10 +303,13 @@ pub fn spawn_attackers(
                 },
                 ..Default::default()
             })
+            .insert(layers)
             .insert(Attacker { handle })
             .insert(AttackerState::Idle(0))
             .insert(FacingDirection::Right)
             .insert(AttackerControls::default())
+            .insert(AttackerCooldowns::default())
+            .insert(Stun::default())
             .insert(Checksum::default())
             .insert(Rollback::new(rip.next_id()))
             .insert(RoundEntity);
@@ -205,10 +321,16 @@ pub fn spawn_defender(
     mut rip: ResMut<RollbackIdProvider>,
     def_sprites: Res<DefenderAssets>,
     misc_sprites: Res<MiscAssets>,
+    mut ammo: ResMut<DefenderAmmo>,
     round_data: Res<RoundData>,
+    settings: Res<MatchSettings>,
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<Level>>,
 ) {
-    let x = DEF_X_POS;
-    let y = GROUND_LEVEL + DEFENDER_SIZE / 2.;
+    let level = levels
+        .get(&level_assets.default_level)
+        .expect("Level not loaded.");
+    let Vec2 { x, y } = level.fortress_spawn;
     commands
         .spawn_bundle(SpriteSheetBundle {
             transform: Transform::from_xyz(x, y, 1.),
@@ -217,10 +339,12 @@ pub fn spawn_defender(
             ..Default::default()
         })
         .insert(Defender {
-            handle: round_data.cur_round as usize,
+            handle: round_data.defender(&settings),
+            cake_type: CakeType::default(),
+            power: 1.,
         })
         .insert(DefenderState::Idle(0))
-        .insert(FacingDirection::Right)
+        .insert(FacingDirection::Left)
         .insert(DefenderControls::default())
         .insert(Checksum::default())
         .insert(Rollback::new(rip.next_id()))
@@ -237,6 +361,9 @@ pub fn spawn_defender(
         .insert(Checksum::default())
         .insert(Rollback::new(rip.next_id()))
         .insert(RoundEntity);
+
+    // every round starts with a full stock of cakes
+    *ammo = DefenderAmmo::default();
 }
 
 pub fn start_round(mut frame_count: ResMut<FrameCount>, mut state: ResMut<RoundState>) {
@@ -251,25 +378,67 @@ pub fn start_round(mut frame_count: ResMut<FrameCount>, mut state: ResMut<RoundS
 
 pub fn update_defender_state(
     mut commands: Commands,
+    settings: Res<MatchSettings>,
     sprites: Res<MiscAssets>,
     mut rip: ResMut<RollbackIdProvider>,
     gravity: Res<Gravity>,
-    mut def_query: Query<(&Transform, &DefenderControls, &mut DefenderState)>,
+    mut stats: ResMut<MatchStats>,
+    mut ammo: ResMut<DefenderAmmo>,
+    mut def_query: Query<(
+        &Transform,
+        &mut Defender,
+        &DefenderControls,
+        &mut DefenderState,
+        &mut FacingDirection,
+        Option<&TripleShot>,
+    )>,
     crosshair_query: Query<&Transform, With<Crosshair>>,
 ) {
     let mut should_shoot = false;
-    let mut cake_x = 0.;
-    let mut cake_y = 0.;
+    let mut shots = 1;
+    let mut muzzle = Vec2::ZERO;
+    let mut cake_type = CakeType::default();
+    let mut power = 1.;
+
+    ammo.update();
+
+    for (t, mut defender, contr, mut state, mut face_dir, triple_shot) in def_query.iter_mut() {
+        // turn towards the crosshair, but not in the middle of a shot
+        if let DefenderState::Idle(_) | DefenderState::Charge(_) = *state {
+            for t_cross in crosshair_query.iter() {
+                if t_cross.translation.x < t.translation.x {
+                    *face_dir = FacingDirection::Left;
+                } else {
+                    *face_dir = FacingDirection::Right;
+                }
+            }
+        }
 
-    for (t, contr, mut state) in def_query.iter_mut() {
         match *state {
             DefenderState::Idle(ref mut f) => {
-                if contr.fire {
-                    *state = DefenderState::Fire(0);
+                // the cake type is locked in once the fortress starts charging
+                if contr.next_cake {
+                    defender.cake_type = defender.cake_type.next();
+                }
+                if contr.prev_cake {
+                    defender.cake_type = defender.cake_type.prev();
+                }
+                if contr.fire && ammo.can_fire(defender.cake_type.params().ammo) {
+                    *state = DefenderState::Charge(0);
                     continue;
                 }
                 *f += 1;
             }
+            DefenderState::Charge(ref mut f) => {
+                if contr.fire {
+                    *f += 1;
+                    continue;
+                }
+                // the cakes are used up on release, so the shot can't be fired twice
+                ammo.fire(defender.cake_type.params().ammo);
+                defender.power = DefenderState::charge_power(*f);
+                *state = DefenderState::Fire(0);
+            }
             DefenderState::Fire(ref mut f) => {
                 // fire anim has 4 frames
                 if *f >= FRAMES_PER_SPRITE * 4 {
@@ -279,8 +448,11 @@ pub fn update_defender_state(
                 // fire the cake after the first two frames of animation have played
                 if *f == FRAMES_PER_SPRITE * 2 {
                     should_shoot = true;
-                    cake_x = t.translation.x - DEFENDER_SIZE / 2. + 10.;
-                    cake_y = t.translation.y + 5.;
+                    shots = if triple_shot.is_some() { 3 } else { 1 };
+                    MatchStats::add(&mut stats.cakes_fired, defender.handle, shots);
+                    muzzle = cake_muzzle(t, &face_dir);
+                    cake_type = defender.cake_type;
+                    power = defender.power;
                 }
                 *f += 1;
             }
@@ -289,30 +461,201 @@ pub fn update_defender_state(
 
     for t in crosshair_query.iter() {
         if should_shoot {
-            let dist_x = (t.translation.x - cake_x).min(0.);
-            let dist_y = (t.translation.y - cake_y).max(0.);
-            let cake_vx = 2. * dist_x / JUMP_TIME_TO_PEAK; // TODO: is this correct correct if the crosshair is supposed to be the apex of the parabola?
-            let cake_vy = f32::sqrt(-2. * dist_y * gravity.0.y);
+            let vel = launch_velocity(
+                settings.aim_mode,
+                cake_type,
+                power,
+                muzzle,
+                t.translation.truncate(),
+                gravity.0,
+            );
+            // the middle cake leaves the muzzle, so it still follows the trajectory preview
+            let size = cake_type.params().size;
+            for i in 0..shots {
+                let side = sibling_side(i, shots);
+                spawn_cake(
+                    &mut commands,
+                    &mut rip,
+                    &sprites,
+                    cake_type,
+                    power,
+                    muzzle + Vec2::new(side * (size + SIBLING_CAKE_GAP), 0.),
+                    vel + Vec2::new(side * TRIPLE_SHOT_SPREAD, 0.),
+                );
+            }
+        }
+    }
+}
+
+/// Where the fortress cannon fires cakes from, on the side it is facing
+pub fn cake_muzzle(t: &Transform, face_dir: &FacingDirection) -> Vec2 {
+    let offset = DEFENDER_SIZE / 2. - 10.;
+    let x = match face_dir {
+        FacingDirection::Left => t.translation.x - offset,
+        FacingDirection::Right => t.translation.x + offset,
+    };
+    Vec2::new(x, t.translation.y + 5.)
+}
+
+/// Launch velocity of a cake, also used for the trajectory preview, so it has to stay deterministic
+pub fn launch_velocity(
+    aim_mode: AimMode,
+    cake_type: CakeType,
+    power: f32,
+    muzzle: Vec2,
+    crosshair: Vec2,
+    gravity: Vec2,
+) -> Vec2 {
+    let params = cake_type.params();
+    match aim_mode {
+        AimMode::Apex => {
+            let apex_height = (crosshair.y - muzzle.y) * params.arc * power;
+            ballistics::launch_to_apex(
+                muzzle,
+                Vec2::new(crosshair.x, muzzle.y + apex_height),
+                gravity,
+            )
+        }
+        AimMode::Target => {
+            ballistics::launch_to_target(muzzle, crosshair, gravity, params.flight_time * power)
+        }
+    }
+}
+
+/// Where the i-th of several cakes spawned together goes, relative to the middle one.
+/// They are lined up side by side, overlapping soft bodies would be pushed apart violently.
+fn sibling_side(i: u32, count: u32) -> f32 {
+    i as f32 - (count - 1) as f32 / 2.
+}
+
+/// Spawns the sprite and soft body of a flying cake
+fn spawn_cake(
+    commands: &mut Commands,
+    rip: &mut RollbackIdProvider,
+    sprites: &MiscAssets,
+    cake_type: CakeType,
+    power: f32,
+    pos: Vec2,
+    vel: Vec2,
+) {
+    let body = rip.next_id();
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: cake_type.params().color,
+                ..Default::default()
+            },
+            texture: sprites.cake.clone(),
+            transform: Transform::from_xyz(pos.x, pos.y, 10.),
+            ..Default::default()
+        })
+        .insert(Cake {
+            body,
+            bounces: 0,
+            power,
+        })
+        .insert(cake_type)
+        .insert(CakeState::Flying(0))
+        .insert(Rollback::new(body))
+        .insert(RoundEntity);
+    spawn_cake_body(commands, rip, body, cake_type.params(), pos, vel);
+}
+
+/// Spawns the particles and distance constraints making up the soft body of a cake
+fn spawn_cake_body(
+    commands: &mut Commands,
+    rip: &mut RollbackIdProvider,
+    body: u32,
+    params: &CakeParams,
+    pos: Vec2,
+    vel: Vec2,
+) {
+    let n = CAKE_PARTICLES_PER_SIDE;
+    let radius = params.size / (2 * n) as f32;
+    let spacing = 2. * radius;
+    let offset = Vec2::splat(params.size / 2. - radius);
+    let index = |x: u32, y: u32| y * n + x;
+
+    for y in 0..n {
+        for x in 0..n {
             commands
-                .spawn_bundle(SpriteBundle {
-                    texture: sprites.cake.clone(),
-                    transform: Transform::from_xyz(cake_x, cake_y, 10.),
+                .spawn_bundle(ParticleBundle {
+                    pos: Pos(pos - offset + Vec2::new(x as f32, y as f32) * spacing),
+                    mass: Mass(params.particle_mass()),
+                    collider: CircleCollider { radius },
+                    vel: Vel(vel),
+                    restitution: Restitution(params.restitution),
                     ..Default::default()
                 })
-                .insert_bundle(DynamicBoxBundle {
-                    pos: Pos(Vec2::new(cake_x, cake_y)),
-                    collider: BoxCollider {
-                        size: Vec2::new(CAKE_SIZE, CAKE_SIZE),
-                    },
-                    vel: Vel(Vec2::new(cake_vx, cake_vy)),
-                    ..Default::default()
+                .insert(SoftBodyParticle {
+                    body,
+                    index: index(x, y),
                 })
-                .insert(Cake)
+                .insert(CakeParticle)
                 .insert(Checksum::default())
                 .insert(Rollback::new(rip.next_id()))
                 .insert(RoundEntity);
         }
     }
+
+    // structural constraints between neighbours, shear constraints along the diagonals
+    let mut constraints = Vec::new();
+    for y in 0..n {
+        for x in 0..n {
+            if x + 1 < n {
+                constraints.push((index(x, y), index(x + 1, y), spacing));
+            }
+            if y + 1 < n {
+                constraints.push((index(x, y), index(x, y + 1), spacing));
+            }
+            if x + 1 < n && y + 1 < n {
+                constraints.push((index(x, y), index(x + 1, y + 1), spacing * SQRT_2));
+                constraints.push((index(x + 1, y), index(x, y + 1), spacing * SQRT_2));
+            }
+        }
+    }
+    for (a, b, rest_length) in constraints {
+        commands
+            .spawn()
+            .insert(DistanceConstraint {
+                body,
+                a,
+                b,
+                rest_length,
+                compliance: CAKE_COMPLIANCE,
+            })
+            .insert(Rollback::new(rip.next_id()))
+            .insert(RoundEntity);
+    }
+}
+
+/// Moves the cake sprite to the center of its particles and squishes it along with the soft body
+pub fn update_cake_bodies(
+    mut cakes: Query<(&Cake, &CakeType, &mut Transform)>,
+    particles: Query<(&Pos, &SoftBodyParticle), With<CakeParticle>>,
+) {
+    for (cake, cake_type, mut t) in cakes.iter_mut() {
+        let radius = cake_type.params().size / (2 * CAKE_PARTICLES_PER_SIDE) as f32;
+        let mut min = Vec2::splat(f32::MAX);
+        let mut max = Vec2::splat(f32::MIN);
+        let mut sum = Vec2::ZERO;
+        let mut count = 0;
+        for (pos, _) in particles.iter().filter(|(_, p)| p.body == cake.body) {
+            min = min.min(pos.0);
+            max = max.max(pos.0);
+            sum += pos.0;
+            count += 1;
+        }
+        if count == 0 {
+            continue;
+        }
+
+        let center = sum / count as f32;
+        let extents = max - min + Vec2::splat(2. * radius);
+        t.translation.x = center.x;
+        t.translation.y = center.y;
+        t.scale = (extents / CAKE_SIZE).extend(1.);
+    }
 }
 
 /// Needs to happen before input
@@ -321,15 +664,27 @@ pub fn update_attacker_state(
     // just check against statics (ground) for now
     contacts: Res<Contacts>,
     static_contacts: Res<StaticContacts>,
+    settings: Res<MatchSettings>,
+    mut stats: ResMut<MatchStats>,
     mut query: Query<(
         Entity,
-        &Vel,
+        &Attacker,
+        &mut Vel,
         &AttackerControls,
         &mut AttackerState,
         &mut FacingDirection,
+        &mut AttackerCooldowns,
+        &Stun,
     )>,
 ) {
-    for (id, vel, contr, mut state, mut face_dir) in query.iter_mut() {
+    let attackers: Vec<Entity> = query.iter_mut().map(|(id, ..)| id).collect();
+
+    for (id, attacker, mut vel, contr, mut state, mut face_dir, mut cooldowns, stun) in
+        query.iter_mut()
+    {
+        cooldowns.dash = cooldowns.dash.saturating_sub(1);
+        cooldowns.block = cooldowns.block.saturating_sub(1);
+
         // update facing direction
         if contr.horizontal < -IDLE_THRESH {
             *face_dir = FacingDirection::Left;
@@ -339,6 +694,39 @@ pub fn update_attacker_state(
             *face_dir = FacingDirection::Right;
         }
 
+        // janitors running into each other knock each other back
+        if settings.janitor_interaction == JanitorInteraction::Bump && state.can_walk() {
+            let push = contacts.0.iter().find_map(|(a, b, n)| {
+                let horizontal = n.x.abs() > n.y.abs();
+                if *a == id && horizontal && attackers.contains(b) {
+                    Some(-n.x.signum())
+                } else if *b == id && horizontal && attackers.contains(a) {
+                    Some(n.x.signum())
+                } else {
+                    None
+                }
+            });
+            if let Some(dir) = push {
+                vel.0.x = dir * BUMP_SPEED;
+                vel.0.y = BUMP_HOP;
+                *state = AttackerState::Bumped(0);
+                continue;
+            }
+        }
+
+        // abilities
+        if state.can_use_ability() {
+            if contr.dash && cooldowns.dash == 0 {
+                cooldowns.dash = DASH_COOLDOWN;
+                *state = AttackerState::Dash(0);
+                continue;
+            }
+            if contr.block && cooldowns.block == 0 {
+                *state = AttackerState::Block(0);
+                continue;
+            }
+        }
+
         //update state
         match *state {
             AttackerState::Idle(ref mut f) => {
@@ -350,6 +738,10 @@ pub fn update_attacker_state(
                     *state = AttackerState::Jump(0);
                     continue;
                 }
+                if contr.scrub {
+                    *state = AttackerState::Scrub(0);
+                    continue;
+                }
                 if contr.horizontal.abs() > IDLE_THRESH {
                     *state = AttackerState::Walk(0);
                     continue;
@@ -388,6 +780,10 @@ pub fn update_attacker_state(
                     *state = AttackerState::Fall(0);
                     continue;
                 }
+                if contr.scrub {
+                    *state = AttackerState::Scrub(0);
+                    continue;
+                }
                 if *f > LAND_FRAMES {
                     *state = AttackerState::Idle(0);
                     continue;
@@ -403,6 +799,11 @@ pub fn update_attacker_state(
                     *state = AttackerState::Jump(0);
                     continue;
                 }
+                // swing the mop while walking, scrub while standing still
+                if contr.scrub {
+                    *state = AttackerState::Swipe(0);
+                    continue;
+                }
                 if contr.horizontal.abs() < IDLE_THRESH {
                     *state = AttackerState::Idle(0);
                     continue;
@@ -410,7 +811,48 @@ pub fn update_attacker_state(
                 *f += 1;
             }
             AttackerState::Hit(ref mut f) => {
-                if *f > STUN_FRAMES {
+                if *f > stun.frames {
+                    *state = AttackerState::Idle(0);
+                    continue;
+                }
+                MatchStats::add(&mut stats.stun_frames, attacker.handle, 1);
+                *f += 1;
+            }
+            AttackerState::Bumped(ref mut f) => {
+                if *f > BUMP_FRAMES {
+                    *state = AttackerState::Idle(0);
+                    continue;
+                }
+                *f += 1;
+            }
+            AttackerState::Scrub(ref mut f) => {
+                if vel.0.y < -IDLE_THRESH {
+                    *state = AttackerState::Fall(0);
+                    continue;
+                }
+                if !contr.scrub {
+                    *state = AttackerState::Idle(0);
+                    continue;
+                }
+                *f += 1;
+            }
+            AttackerState::Swipe(ref mut f) => {
+                if *f > SWIPE_FRAMES {
+                    *state = AttackerState::Idle(0);
+                    continue;
+                }
+                *f += 1;
+            }
+            AttackerState::Dash(ref mut f) => {
+                if *f > DASH_FRAMES {
+                    *state = AttackerState::Idle(0);
+                    continue;
+                }
+                *f += 1;
+            }
+            AttackerState::Block(ref mut f) => {
+                if !contr.block || *f > BLOCK_FRAMES {
+                    cooldowns.block = BLOCK_COOLDOWN;
                     *state = AttackerState::Idle(0);
                     continue;
                 }
@@ -439,7 +881,17 @@ pub fn apply_attacker_inputs(
             0.
         };
 
-        controls.vertical = if input & INPUT_DOWN != 0 && input & INPUT_UP == 0 {
+        // the action button scrubs (or swipes the mop while walking),
+        // together with up it dashes and together with down it blocks
+        let act = input & INPUT_ACT != 0;
+        controls.dash = act && input & INPUT_UP != 0;
+        controls.block = act && input & INPUT_DOWN != 0;
+        controls.scrub = act && !controls.dash && !controls.block;
+
+        // up only jumps when it doesn't pick an ability
+        controls.vertical = if act {
+            0.
+        } else if input & INPUT_DOWN != 0 && input & INPUT_UP == 0 {
             -1. // up positive
         } else if input & INPUT_DOWN == 0 && input & INPUT_UP != 0 {
             1.
@@ -477,19 +929,66 @@ pub fn apply_defender_inputs(
         };
 
         controls.fire = input & INPUT_ACT != 0;
+        controls.roll = input & INPUT_ROLL != 0;
+
+        // only the frame a button goes down counts, holding it doesn't keep cycling
+        let pressed = input & !controls.last_input;
+        controls.next_cake = pressed & INPUT_ALT2 != 0;
+        controls.prev_cake = pressed & INPUT_ALT1 != 0;
+        controls.last_input = input;
     }
 }
 
 pub fn move_attackers(
-    mut query: Query<(&mut Vel, &AttackerState, &AttackerControls), With<Rollback>>,
+    mut query: Query<
+        (
+            &mut Vel,
+            &AttackerState,
+            &AttackerControls,
+            &FacingDirection,
+            &Pos,
+            Option<&SpeedBoost>,
+        ),
+        With<Rollback>,
+    >,
+    puddles: Query<(&Transform, &Puddle)>,
     gravity: Res<Gravity>,
 ) {
-    for (mut vel, state, controls) in query.iter_mut() {
+    for (mut vel, state, controls, face_dir, pos, speed_boost) in query.iter_mut() {
+        // bumped janitors slide out of the collision
+        if let AttackerState::Bumped(..) = state {
+            vel.0.x *= BUMP_DAMPING;
+            continue;
+        }
+
+        // dashing ignores gravity
+        if let AttackerState::Dash(..) = state {
+            vel.0.x = match face_dir {
+                FacingDirection::Left => -DASH_SPEED,
+                FacingDirection::Right => DASH_SPEED,
+            };
+            vel.0.y = 0.;
+            continue;
+        }
+
         // just set horizontal velocity for now
         // this totally overwrites any velocity on the x axis, which might not be ideal...
-        vel.0.x = 0.;
+        let mut target = 0.;
         if state.can_walk() {
-            vel.0.x = controls.horizontal * MAX_SPEED;
+            target = controls.horizontal * MAX_SPEED;
+            if speed_boost.is_some() {
+                target *= SPEED_BOOST;
+            }
+        }
+        // on a puddle, janitors only slowly get going or come to a stop
+        let feet = pos.0 - Vec2::new(0., ATTACKER_SIZE / 2.);
+        let slipping = puddles
+            .iter()
+            .any(|(t, puddle)| puddle.under(t.translation.truncate(), feet));
+        if slipping {
+            vel.0.x += (target - vel.0.x) * PUDDLE_GRIP;
+        } else {
+            vel.0.x = target;
         }
 
         if controls.vertical > 0. && state.can_jump() {
@@ -514,7 +1013,10 @@ pub fn move_crosshair(
     let mut vert = 0.;
 
     for c in input_query.iter() {
-        hor = c.horizontal;
+        // while rolling, left and right move the fortress instead, see move_defender
+        if !c.roll {
+            hor = c.horizontal;
+        }
         vert = c.vertical;
     }
 
@@ -527,91 +1029,542 @@ pub fn move_crosshair(
     }
 }
 
+pub fn move_defender(
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<Level>>,
+    mut query: Query<(&mut Transform, &DefenderControls), With<Defender>>,
+) {
+    let level = levels
+        .get(&level_assets.default_level)
+        .expect("Level not loaded.");
+    let (min_x, max_x) = level.fortress_track;
+
+    for (mut t, contr) in query.iter_mut() {
+        if contr.roll {
+            t.translation.x += contr.horizontal * FORTRESS_SPEED;
+            t.translation.x = t.translation.x.clamp(min_x, max_x);
+        }
+    }
+}
+
 pub fn cake_collision(
     mut commands: Commands,
     contacts: Res<Contacts>,
     static_contacts: Res<StaticContacts>,
     mut rip: ResMut<RollbackIdProvider>,
-    frame_count: Res<FrameCount>,
+    mut rng: ResMut<MatchRng>,
+    round_data: Res<RoundData>,
+    settings: Res<MatchSettings>,
+    mut stats: ResMut<MatchStats>,
     misc_sprites: Res<MiscAssets>,
-    mut attackers: Query<(Entity, &mut AttackerState)>,
-    cakes: Query<(Entity, &Transform), With<Cake>>,
+    mut attackers: Query<(
+        Entity,
+        &Attacker,
+        &Transform,
+        &mut AttackerState,
+        &mut Stun,
+        Option<&StunImmunity>,
+    )>,
+    big_splats: Query<&BigSplats>,
+    mut cakes: Query<(
+        Entity,
+        &mut Cake,
+        &CakeType,
+        &mut CakeState,
+        &Transform,
+        Option<&CakeImpact>,
+        Option<&SplatSurface>,
+    )>,
+    particles: Query<(Entity, &SoftBodyParticle), With<CakeParticle>>,
+    mut particle_vels: Query<&mut Vel, With<CakeParticle>>,
+    constraints: Query<(Entity, &DistanceConstraint)>,
+    statics: Query<(&Pos, &BoxCollider), Without<Mass>>,
 ) {
-    for (cake, t) in cakes.iter() {
-        let mut cake_collided = false;
-        //check for attacker collision
-        for (attacker, mut state) in attackers.iter_mut() {
-            if contacts
-                .0
-                .iter()
-                .any(|(a, c, _)| *a == attacker && *c == cake)
-            {
-                if !state.is_stunned() {
-                    *state = AttackerState::Hit(0);
+    let defender = round_data.defender(&settings);
+    for (cake, mut cake_body, cake_type, mut cake_state, t, impact, surface) in cakes.iter_mut() {
+        let params = cake_type.params();
+        let cake_particles: Vec<Entity> = particles
+            .iter()
+            .filter(|(_, p)| p.body == cake_body.body)
+            .map(|(e, _)| e)
+            .collect();
+
+        match *cake_state {
+            CakeState::Flying(ref mut f) => {
+                *f += 1;
+
+                // cluster cakes split into crumbs mid-air
+                if params.fragments > 0 && *f >= params.split_frame {
+                    despawn_cake(
+                        &mut commands,
+                        cake,
+                        &cake_particles,
+                        cake_body.body,
+                        &constraints,
+                    );
+                    let vels: Vec<Vec2> = cake_particles
+                        .iter()
+                        .filter_map(|p| particle_vels.get_mut(*p).ok().map(|v| v.0))
+                        .collect();
+                    let vel = vels.iter().sum::<Vec2>() / vels.len().max(1) as f32;
+                    // every crumb can hit on its own, so each one counts as a cake fired
+                    MatchStats::add(&mut stats.cakes_fired, defender, params.fragments - 1);
+                    let crumb_size = CakeType::Crumb.params().size;
+                    for i in 0..params.fragments {
+                        let side = sibling_side(i, params.fragments);
+                        spawn_cake(
+                            &mut commands,
+                            &mut rip,
+                            &misc_sprites,
+                            CakeType::Crumb,
+                            cake_body.power,
+                            t.translation.truncate()
+                                + Vec2::new(side * (crumb_size + SIBLING_CAKE_GAP), 0.),
+                            vel + Vec2::new(side * CLUSTER_SPREAD_SPEED, 0.),
+                        );
+                    }
+                    continue;
+                }
+
+                let mut cake_collided = false;
+                //check for attacker collision
+                for (entity, attacker, t_attacker, mut state, mut stun, immunity) in
+                    attackers.iter_mut()
+                {
+                    if contacts.0.iter().any(|(a, b, _)| {
+                        (*a == entity && cake_particles.contains(b))
+                            || (*b == entity && cake_particles.contains(a))
+                    }) {
+                        // blocking janitors send the cake flying back
+                        if let AttackerState::Block(..) = *state {
+                            let dir = (t.translation.x - t_attacker.translation.x).signum();
+                            for particle in cake_particles.iter() {
+                                if let Ok(mut vel) = particle_vels.get_mut(*particle) {
+                                    vel.0 = Vec2::new(dir, 0.5) * BLOCK_DEFLECT_SPEED;
+                                }
+                            }
+                            continue;
+                        }
+                        if !state.is_stunned() && immunity.is_none() {
+                            *state = AttackerState::Hit(0);
+                            stun.frames = params.stun_frames;
+                            MatchStats::add(&mut stats.cake_hits, attacker.handle, 1);
+                        }
+                        cake_collided = true;
+                    }
+                }
+                if cake_collided {
+                    MatchStats::add(&mut stats.cakes_on_target, defender, 1);
+                }
+                // check for ground or platform collision
+                let surface = highest_surface(
+                    static_contacts
+                        .0
+                        .iter()
+                        .filter(|(c, _, n)| cake_particles.contains(c) && n.y < 0.)
+                        .filter_map(|(_, s, _)| statics.get(*s).ok())
+                        .map(|(pos, collider)| SplatSurface::top_of(pos.0, collider.size)),
+                );
+                // bouncy cakes only splat once they run out of bounces
+                if !cake_collided && surface.is_some() && cake_body.bounces < params.bounces {
+                    // a bounce can touch the ground for a few frames, count it once
+                    if *f > BOUNCE_FRAMES {
+                        cake_body.bounces += 1;
+                        *f = 0;
+                    }
+                    continue;
+                }
+                // squish a bit before splatting
+                if cake_collided || surface.is_some() {
+                    // without a surface, the cake hit a janitor in mid-air
+                    if let Some(surface) = surface {
+                        commands.entity(cake).insert(surface);
+                    }
+                    commands.entity(cake).insert(CakeImpact {
+                        pos: t.translation.truncate(),
+                    });
+                    *cake_state = CakeState::Squish(0);
                 }
-                cake_collided = true;
             }
-        }
-        // check for ground collision
-        if static_contacts
-            .0
-            .iter()
-            .any(|(c, _, n)| *c == cake && n.y < 0.)
-        {
-            cake_collided = true;
-        }
-        // splat
-        if cake_collided {
-            commands.entity(cake).despawn_recursive();
-
-            let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(frame_count.frame as u64);
-            for i in 0..rng.gen_range(MIN_SPLAT..MAX_SPLAT) {
-                let rand_splat = rng.gen::<f32>() * 2. - 1.; // between -1 and 1
-                let mut x_pos: f32 = t.translation.x + rand_splat * SPLAT_SPREAD;
-                x_pos = x_pos.clamp(-SCREEN_X / 4. + 13., SCREEN_X / 4.);
-                let splat_sprite = if i % 2 == 0 {
-                    misc_sprites.splat1.clone()
+            CakeState::Squish(ref mut f) => {
+                if *f < CAKE_SQUISH_FRAMES {
+                    *f += 1;
+                    continue;
+                }
+
+                // splat
+                despawn_cake(
+                    &mut commands,
+                    cake,
+                    &cake_particles,
+                    cake_body.body,
+                    &constraints,
+                );
+
+                // the cake keeps moving while it squishes, the splats start where it hit
+                let impact = impact.map_or(t.translation.truncate(), |i| i.pos);
+                // on a surface, the splats are spread along it, otherwise they fall down
+                let (min_x, max_x) = match surface {
+                    Some(s) => (s.min_x, s.max_x),
+                    None => (f32::MIN, f32::MAX),
+                };
+                let min_x = min_x.max(-SCREEN_X / 4.) + 13.;
+                let max_x = max_x.min(SCREEN_X / 4.).max(min_x);
+
+                let splat_scale = if big_splats.is_empty() {
+                    params.splat_scale
                 } else {
-                    misc_sprites.splat2.clone()
+                    params.splat_scale * BIG_SPLAT_SCALE
                 };
-                commands
-                    .spawn_bundle(SpriteBundle {
+                let num_splats = rng.gen_range(params.min_splat..params.max_splat);
+                let num_splats = ((num_splats as f32 * cake_body.power).round() as u32).max(1);
+                MatchStats::add(&mut stats.splats_spawned, defender, num_splats);
+                for i in 0..num_splats {
+                    let rand_splat = rng.gen::<f32>() * 2. - 1.; // between -1 and 1
+                    let mut x_pos: f32 = impact.x + rand_splat * params.splat_spread;
+                    x_pos = x_pos.clamp(min_x, max_x);
+                    // bigger splats take longer to clean
+                    let scale = rng.gen_range(MIN_SPLAT_SCALE..MAX_SPLAT_SCALE) * splat_scale;
+                    let dirt = (SPLAT_DIRT * scale) as u32;
+                    let y_pos = match surface {
+                        Some(s) => s.y + SPLAT_OFFSET * scale,
+                        None => impact.y,
+                    };
+                    let splat_sprite = if i % 2 == 0 {
+                        misc_sprites.splat1.clone()
+                    } else {
+                        misc_sprites.splat2.clone()
+                    };
+                    let mut splat = commands.spawn_bundle(SpriteBundle {
                         texture: splat_sprite,
-                        transform: Transform::from_xyz(x_pos, GROUND_LEVEL + 12., 10.),
+                        transform: Transform {
+                            translation: Vec3::new(x_pos, y_pos, 10.),
+                            scale: Vec3::new(scale, scale, 1.),
+                            ..Default::default()
+                        },
                         ..Default::default()
-                    })
-                    .insert(Splat)
-                    .insert(Checksum::default())
-                    .insert(Rollback::new(rip.next_id()))
-                    .insert(RoundEntity);
+                    });
+                    splat
+                        .insert(Splat {
+                            dirt,
+                            max_dirt: dirt,
+                        })
+                        .insert(Checksum::default())
+                        .insert(Rollback::new(rip.next_id()))
+                        .insert(RoundEntity);
+                    if surface.is_none() {
+                        drop_splat(
+                            &mut splat,
+                            Vec2::new(x_pos, y_pos),
+                            scale,
+                            Vec2::new(rand_splat * params.splat_spread, 0.),
+                        );
+                    }
+                }
             }
         }
     }
 }
 
+/// Makes a splat fall down, until it lands on something, see settle_splats
+fn drop_splat(splat: &mut EntityCommands, pos: Vec2, scale: f32, vel: Vec2) {
+    // falling splats only collide with the level
+    splat
+        .insert_bundle(ParticleBundle {
+            pos: Pos(pos),
+            prev_pos: PrevPos(pos),
+            collider: CircleCollider {
+                radius: SPLAT_OFFSET * scale,
+            },
+            vel: Vel(vel),
+            ..Default::default()
+        })
+        .insert(CollisionLayers::new(LAYER_SPLAT, 0));
+}
+
+fn despawn_cake(
+    commands: &mut Commands,
+    cake: Entity,
+    cake_particles: &[Entity],
+    body: u32,
+    constraints: &Query<(Entity, &DistanceConstraint)>,
+) {
+    commands.entity(cake).despawn_recursive();
+    for particle in cake_particles.iter() {
+        commands.entity(*particle).despawn_recursive();
+    }
+    for (constraint, c) in constraints.iter() {
+        if c.body == body {
+            commands.entity(constraint).despawn_recursive();
+        }
+    }
+}
+
+/// Splats that fell off a janitor stop being physics bodies once they land on something
+pub fn settle_splats(
+    mut commands: Commands,
+    static_contacts: Res<StaticContacts>,
+    splats: Query<Entity, (With<Splat>, With<Mass>)>,
+) {
+    for splat in splats.iter() {
+        if static_contacts
+            .0
+            .iter()
+            .any(|(s, _, n)| *s == splat && n.y < 0.)
+        {
+            commands
+                .entity(splat)
+                .remove_bundle::<ParticleBundle>()
+                .remove::<CollisionLayers>();
+        }
+    }
+}
+
+// ties go to the surface that was found first, so the result only depends on the contact order
+fn highest_surface(surfaces: impl Iterator<Item = SplatSurface>) -> Option<SplatSurface> {
+    surfaces.reduce(|highest, s| if s.y > highest.y { s } else { highest })
+}
+
 pub fn splat_cleaning(
     mut commands: Commands,
-    attackers: Query<(&Transform, &AttackerState), With<Attacker>>,
-    splats: Query<(Entity, &Transform), With<Splat>>,
+    mut stats: ResMut<MatchStats>,
+    mut round_data: ResMut<RoundData>,
+    attackers: Query<(
+        &Transform,
+        &Attacker,
+        &AttackerState,
+        &FacingDirection,
+        Option<&AreaClean>,
+    )>,
+    mut splats: Query<(Entity, &Transform, &mut Splat), Without<Mass>>,
 ) {
-    for (t_attack, state) in attackers.iter() {
-        if !state.can_clean() {
+    // falling splats can't be cleaned yet
+    for (entity, t_splat, mut splat) in splats.iter_mut() {
+        // (handle, dirt removed this frame)
+        let scrubbers: Vec<(usize, u32)> = attackers
+            .iter()
+            .filter_map(|(t_attack, attacker, state, face_dir, area_clean)| {
+                let dist = t_splat.translation.truncate() - t_attack.translation.truncate();
+                if area_clean.is_some() && dist.length() < AREA_CLEAN_RADIUS {
+                    return Some((attacker.handle, SWIPE_RATE));
+                }
+                // janitors only reach splats on the surface they are standing on
+                if (t_splat.translation.y - t_attack.translation.y).abs() >= ATTACKER_SIZE / 2. {
+                    return None;
+                }
+                let dx = t_splat.translation.x - t_attack.translation.x;
+                match state {
+                    AttackerState::Scrub(..) if dx.abs() < SCRUB_REACH => {
+                        Some((attacker.handle, 1))
+                    }
+                    // the mop reaches further in front of the janitor
+                    AttackerState::Swipe(..) => {
+                        let forward = match face_dir {
+                            FacingDirection::Left => -dx,
+                            FacingDirection::Right => dx,
+                        };
+                        if forward > -SCRUB_REACH && forward < SWIPE_REACH {
+                            Some((attacker.handle, SWIPE_RATE))
+                        } else {
+                            None
+                        }
+                    }
+                    _ => None,
+                }
+            })
+            .collect();
+        if scrubbers.is_empty() {
             continue;
         }
 
-        for (splat, t_splat) in splats.iter() {
-            if (t_splat.translation.x - t_attack.translation.x).abs() < 1. {
-                commands.entity(splat).despawn_recursive();
+        // janitors scrubbing together clean faster
+        let rate: u32 = scrubbers.iter().map(|(_, rate)| rate).sum();
+        splat.dirt = splat.dirt.saturating_sub(rate);
+        if splat.dirt == 0 {
+            commands.entity(entity).despawn_recursive();
+            // if several janitors finish the splat at once, the lowest handle gets the credit
+            let handle = scrubbers.iter().map(|(handle, _)| *handle).min().unwrap();
+            MatchStats::add(&mut stats.splats_cleaned, handle, 1);
+            round_data.add_cleaned(handle);
+        }
+    }
+}
+
+/// Every PICKUP_INTERVAL frames, a random pickup shows up somewhere in the arena
+pub fn spawn_pickups(
+    mut commands: Commands,
+    mut rip: ResMut<RollbackIdProvider>,
+    frame_count: Res<FrameCount>,
+    mut rng: ResMut<MatchRng>,
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<Level>>,
+    pickups: Query<&Pickup>,
+) {
+    if frame_count.frame == 0
+        || frame_count.frame % PICKUP_INTERVAL != 0
+        || pickups.iter().count() >= MAX_PICKUPS
+    {
+        return;
+    }
+
+    let level = levels
+        .get(&level_assets.default_level)
+        .expect("Level not loaded.");
+    let floors: Vec<Floor> = level
+        .floors()
+        .into_iter()
+        .filter(|f| f.max_x - f.min_x > PICKUP_SIZE)
+        .collect();
+    if floors.is_empty() {
+        return;
+    }
+
+    let pickup = Pickup::ALL[rng.gen_range(0..Pickup::ALL.len())];
+    // janitor pickups lie on a floor, fortress pickups float where cakes fly
+    let (min_x, max_x, y) = if pickup.for_janitors() {
+        let floor = floors[rng.gen_range(0..floors.len())];
+        (floor.min_x, floor.max_x, floor.y + PICKUP_SIZE / 2.)
+    } else {
+        let min_x = floors.iter().map(|f| f.min_x).fold(f32::MAX, f32::min);
+        let max_x = floors.iter().map(|f| f.max_x).fold(f32::MIN, f32::max);
+        (
+            min_x,
+            max_x,
+            rng.gen_range(0.0..SCREEN_Y / 4. - PICKUP_SIZE),
+        )
+    };
+    let x = rng.gen_range((min_x + PICKUP_SIZE / 2.)..(max_x - PICKUP_SIZE / 2.));
+    // something else is in the way, try again next time
+    if level.blocked(Vec2::new(x, y), Vec2::splat(PICKUP_SIZE)) {
+        return;
+    }
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: PICKUP_COLORS[pickup as usize],
+                custom_size: Some(Vec2::splat(PICKUP_SIZE)),
+                ..Default::default()
+            },
+            transform: Transform::from_xyz(x, y, 8.),
+            ..Default::default()
+        })
+        .insert(pickup)
+        .insert(Rollback::new(rip.next_id()))
+        .insert(RoundEntity);
+}
+
+pub fn collect_pickups(
+    mut commands: Commands,
+    pickups: Query<(Entity, &Pickup, &Transform)>,
+    attackers: Query<(Entity, &Attacker, &Transform)>,
+    defenders: Query<Entity, With<Defender>>,
+    cakes: Query<(&Transform, &CakeType), With<Cake>>,
+) {
+    for (entity, pickup, t) in pickups.iter() {
+        let pos = t.translation.truncate();
+        let collector = if pickup.for_janitors() {
+            // if several janitors reach it at once, the lowest handle gets it
+            attackers
+                .iter()
+                .filter(|(_, _, t_attack)| {
+                    let dist = (t_attack.translation.truncate() - pos).abs();
+                    dist.max_element() < (ATTACKER_SIZE + PICKUP_SIZE) / 2.
+                })
+                .min_by_key(|(_, attacker, _)| attacker.handle)
+                .map(|(janitor, ..)| janitor)
+        } else if cakes.iter().any(|(t_cake, cake_type)| {
+            let dist = (t_cake.translation.truncate() - pos).abs();
+            dist.max_element() < (cake_type.params().size + PICKUP_SIZE) / 2.
+        }) {
+            defenders.iter().next()
+        } else {
+            None
+        };
+
+        if let Some(collector) = collector {
+            commands.entity(entity).despawn_recursive();
+            let frames = PICKUP_FRAMES;
+            let mut collector = commands.entity(collector);
+            // picking up the same thing again starts the effect over
+            match pickup {
+                Pickup::SpeedBoost => collector.insert(SpeedBoost { frames }),
+                Pickup::StunImmunity => collector.insert(StunImmunity { frames }),
+                Pickup::AreaClean => collector.insert(AreaClean { frames }),
+                Pickup::TripleShot => collector.insert(TripleShot { frames }),
+                Pickup::BigSplats => collector.insert(BigSplats { frames }),
+            };
+        }
+    }
+}
+
+/// Counts down a pickup effect and removes it once it ran out
+pub fn tick_pickup_effect<T: Component + PickupEffect>(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut T)>,
+) {
+    for (entity, mut effect) in query.iter_mut() {
+        let frames = effect.frames_mut();
+        *frames = frames.saturating_sub(1);
+        if *frames == 0 {
+            commands.entity(entity).remove::<T>();
+        }
+    }
+}
+
+/// Conveyors push janitors standing on them and carry splats along, until they drop off the end
+pub fn move_on_conveyors(
+    mut commands: Commands,
+    static_contacts: Res<StaticContacts>,
+    conveyors: Query<(&Pos, &BoxCollider, &Conveyor), Without<Attacker>>,
+    mut janitors: Query<(Entity, &mut Pos), With<Attacker>>,
+    mut splats: Query<(Entity, &mut Transform), (With<Splat>, Without<Mass>)>,
+) {
+    // janitors are moved directly, so the belt doesn't add up with their own velocity
+    for (janitor, mut pos) in janitors.iter_mut() {
+        // standing on two conveyors at once, the first one wins
+        let conveyor = static_contacts
+            .0
+            .iter()
+            .filter(|(e, _, n)| *e == janitor && n.y < 0.)
+            .find_map(|(_, s, _)| conveyors.get(*s).ok());
+        if let Some((_, _, conveyor)) = conveyor {
+            pos.0.x += conveyor.speed * DELTA_TIME;
+        }
+    }
+
+    for (pos, collider, conveyor) in conveyors.iter() {
+        let top = SplatSurface::top_of(pos.0, collider.size);
+        for (splat, mut t) in splats.iter_mut() {
+            let scale = t.scale.x;
+            let on_top = (t.translation.y - SPLAT_OFFSET * scale - top.y).abs() < 0.5
+                && t.translation.x >= top.min_x
+                && t.translation.x <= top.max_x;
+            if !on_top {
+                continue;
+            }
+            t.translation.x += conveyor.speed * DELTA_TIME;
+            if t.translation.x < top.min_x || t.translation.x > top.max_x {
+                drop_splat(
+                    &mut commands.entity(splat),
+                    t.translation.truncate(),
+                    scale,
+                    Vec2::new(conveyor.speed, 0.),
+                );
             }
         }
     }
 }
 
-pub fn check_round_end(mut frame_count: ResMut<FrameCount>, mut round_state: ResMut<RoundState>) {
+pub fn check_round_end(
+    mut frame_count: ResMut<FrameCount>,
+    mut round_state: ResMut<RoundState>,
+    round_data: Res<RoundData>,
+    settings: Res<MatchSettings>,
+) {
     frame_count.frame += 1;
 
-    // game ends after ROUND_LENGTH frames
-    if frame_count.frame >= ROUND_LENGTH {
+    // game ends after round_length frames
+    if frame_count.frame >= round_data.round_length(&settings) {
         *round_state = RoundState::RoundEnd;
     }
 
@@ -627,9 +1580,11 @@ pub fn cleanup_round(
     splats: Query<Entity, With<Splat>>,
     query: Query<Entity, With<RoundEntity>>,
     local_handles: Res<LocalHandles>,
+    settings: Res<MatchSettings>,
     mut frame_count: ResMut<FrameCount>,
     mut round_state: ResMut<RoundState>,
     mut round_data: ResMut<RoundData>,
+    stats: Res<MatchStats>,
     mut app_state: ResMut<State<AppState>>,
     mut commands: Commands,
 ) {
@@ -644,30 +1599,178 @@ pub fn cleanup_round(
     round_data.results.insert(round, splat_count);
     round_data.cur_round += 1; // update round information
 
-    if round_data.cur_round >= NUM_ROUNDS {
-        // determine winner
-        let winner = round_data.winner();
-        let winner_str = if local_handles.handles.len() == 1 {
-            // online mode
-            if local_handles.handles[0] == winner as usize {
-                "You lost!".to_owned()
-            } else {
-                "You won!".to_owned()
-            }
-        } else {
-            // local mode
-            format!("\nJanitor {} wins!", winner + 1)
-        };
-        let mut result_str = round_data.to_string();
-        result_str.push_str(&winner_str);
-        // go to win screen
-        match app_state.set(AppState::Win) {
-            Ok(_) => commands.insert_resource(MatchResult { result: result_str }),
-            Err(e) => warn!("Could not change app state to AppState::Win : {}", e), // this happens when there is a rollback and the change to app win is queued twice
-        };
-    } else {
+    if !round_data.match_over(&settings) {
         // start another round
         *round_state = RoundState::InterludeStart;
+        return;
+    }
+
+    // determine winner
+    let winners = round_data.winners(&settings);
+    if winners.len() > 1 && settings.tie_break == TieBreak::SuddenDeath && !round_data.sudden_death
+    {
+        // break the tie with one more leg
+        round_data.start_sudden_death();
+        *round_state = RoundState::InterludeStart;
+        return;
     }
+
+    let result_str = round_data.summary(&settings);
+    *round_state = RoundState::MatchEnd;
+    end_match(
+        result_str,
+        &winners,
+        &local_handles,
+        &settings,
+        &stats,
+        &mut app_state,
+        &mut commands,
+    );
     //println!("\nROUND END {:?}", *round_data);
 }
+
+/// Players can surrender at any time and disconnected players forfeit the match.
+/// Only confirmed inputs count, so a misprediction can never end the match.
+pub fn check_forfeit(
+    inputs: Res<Vec<(Input, InputStatus)>>,
+    local_handles: Res<LocalHandles>,
+    settings: Res<MatchSettings>,
+    mut round_state: ResMut<RoundState>,
+    round_data: Res<RoundData>,
+    stats: Res<MatchStats>,
+    round_world: Query<Entity, Or<(With<RoundEntity>, With<Interlude>)>>,
+    mut app_state: ResMut<State<AppState>>,
+    mut commands: Commands,
+) {
+    let mut result_str = String::new();
+    let mut forfeits = Vec::new();
+    for (handle, (input, status)) in inputs.iter().enumerate() {
+        match status {
+            InputStatus::Confirmed if input.inp & INPUT_SURRENDER != 0 => {
+                result_str.push_str(&format!("Player {} surrendered!\n", handle + 1));
+                forfeits.push(handle);
+            }
+            InputStatus::Disconnected => {
+                result_str.push_str(&format!("Player {} disconnected!\n", handle + 1));
+                forfeits.push(handle);
+            }
+            _ => (),
+        }
+    }
+
+    if forfeits.is_empty() {
+        return;
+    }
+
+    // the match is decided between the remaining players
+    let remaining: Vec<usize> = (0..settings.num_players)
+        .filter(|handle| !forfeits.contains(handle))
+        .collect();
+    let winners = round_data.winners_among(&remaining, &settings);
+    result_str.push_str(&round_data.summary(&settings));
+
+    // the round is cut short, so cleanup_round never runs for it
+    for e in round_world.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+    *round_state = RoundState::MatchEnd;
+    end_match(
+        result_str,
+        &winners,
+        &local_handles,
+        &settings,
+        &stats,
+        &mut app_state,
+        &mut commands,
+    );
+}
+
+// goes to the win screen
+fn end_match(
+    mut result_str: String,
+    winners: &[usize],
+    local_handles: &LocalHandles,
+    settings: &MatchSettings,
+    stats: &MatchStats,
+    app_state: &mut State<AppState>,
+    commands: &mut Commands,
+) {
+    let winner_str = match winners {
+        [] => "\nNobody wins!".to_owned(),
+        [winner] => {
+            if local_handles.handles.len() == 1 {
+                // online mode
+                if local_handles.handles[0] == *winner {
+                    "You won!".to_owned()
+                } else {
+                    "You lost!".to_owned()
+                }
+            } else {
+                // local mode
+                if settings.team_mode {
+                    format!("\nFortress {} made the biggest mess!", winner + 1)
+                } else {
+                    format!("\nPlayer {} wins!", winner + 1)
+                }
+            }
+        }
+        _ => {
+            let names: Vec<String> = winners.iter().map(|h| (h + 1).to_string()).collect();
+            format!("\nDraw between players {}!", names.join(", "))
+        }
+    };
+    result_str.push_str(&winner_str);
+    match app_state.set(AppState::Win) {
+        Ok(_) => commands.insert_resource(MatchResult {
+            result: result_str,
+            breakdown: stats.breakdown(settings),
+        }),
+        Err(e) => warn!("Could not change app state to AppState::Win : {}", e), // this happens when there is a rollback and the change to app win is queued twice
+    };
+}
+
+/*
+ * MATCH END
+ */
+
+/// Starts a new match once every player asked for a rematch.
+/// The reset happens here, so all players restart on the same frame.
+pub fn check_rematch(
+    inputs: Res<Vec<(Input, InputStatus)>>,
+    settings: Res<MatchSettings>,
+    mut frame_count: ResMut<FrameCount>,
+    mut round_state: ResMut<RoundState>,
+    mut round_data: ResMut<RoundData>,
+    mut stats: ResMut<MatchStats>,
+    round_world: Query<Entity, Or<(With<RoundEntity>, With<Interlude>)>>,
+    mut app_state: ResMut<State<AppState>>,
+    mut commands: Commands,
+) {
+    let all_agreed = inputs.iter().all(|(input, status)| {
+        !matches!(status, InputStatus::Disconnected) && input.inp & INPUT_REMATCH != 0
+    });
+    if !all_agreed {
+        return;
+    }
+
+    // the next player gets to defend first
+    *round_data = RoundData {
+        first_defender: (round_data.first_defender + 1) % settings.num_players,
+        ..Default::default()
+    };
+    *stats = MatchStats::default();
+    frame_count.frame = 0;
+    // whatever is left of the last match would be counted in the first round
+    for e in round_world.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+    *round_state = RoundState::InterludeStart;
+
+    match app_state.set(AppState::RoundOnline) {
+        Ok(_) => commands.insert_resource(Rematch),
+        Err(e) => warn!(
+            "Could not change app state to AppState::RoundOnline : {}",
+            e
+        ), // this happens when there is a rollback and the rematch is started twice
+    };
+}
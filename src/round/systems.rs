@@ -1,56 +1,158 @@
 use bevy::{ecs::schedule::ShouldRun, prelude::*};
 use bevy_ggrs::SessionType;
-use ggrs::{P2PSession, PlayerHandle};
+use ggrs::{P2PSession, PlayerHandle, SyncTestSession};
+use physics::{
+    ballistics,
+    components::ForceZone,
+    prelude::{Gravity, Pos},
+};
 
 use crate::{
-    menu::connect::LocalHandles, AttackerAssets, DefenderAssets, FontAssets, GGRSConfig,
-    MiscAssets, BUTTON_TEXT, NUM_PLAYERS, SCREEN_X, SCREEN_Y,
+    level::{Level, LevelAssets},
+    menu::{
+        connect::LocalHandles,
+        win::{Rematch, RematchRequest},
+    },
+    AttackerAssets, DefenderAssets, FontAssets, GGRSConfig, BUTTON_TEXT, SCREEN_X, SCREEN_Y,
 };
 
 use super::{
-    prelude::*, FRAMES_PER_SPRITE, GROUND_LEVEL, INPUT_ACT, INPUT_DOWN, INPUT_LEFT, INPUT_RIGHT,
-    INPUT_UP, ROUND_LENGTH,
+    prelude::*, DEFENDER_SIZE, FRAMES_PER_SPRITE, GROUND_LEVEL, INPUT_ACT, INPUT_ALT1, INPUT_ALT2,
+    INPUT_DOWN, INPUT_LEFT, INPUT_REMATCH, INPUT_RIGHT, INPUT_ROLL, INPUT_SURRENDER, INPUT_UP,
+    MAX_AMMO, MAX_PLAYERS, MIN_SPLAT_ALPHA, PREVIEW_COLOR, PREVIEW_DOTS, PREVIEW_DOT_SIZE,
+    PREVIEW_DOT_STEPS, SURRENDER_HOLD_FRAMES,
 };
 
+pub struct KeyMap {
+    pub up: KeyCode,
+    pub left: KeyCode,
+    pub down: KeyCode,
+    pub right: KeyCode,
+    pub act: KeyCode,
+    pub alt1: KeyCode,
+    pub alt2: KeyCode,
+    pub roll: KeyCode,
+    pub move_name: &'static str,
+    pub act_name: &'static str,
+    pub alt_name: &'static str,
+    pub roll_name: &'static str,
+}
+
+// keymaps for the local players, in order of their local handles
+pub const KEYMAPS: [KeyMap; MAX_PLAYERS] = [
+    KeyMap {
+        up: KeyCode::W,
+        left: KeyCode::A,
+        down: KeyCode::S,
+        right: KeyCode::D,
+        act: KeyCode::Space,
+        alt1: KeyCode::Q,
+        alt2: KeyCode::E,
+        roll: KeyCode::LShift,
+        move_name: "WASD",
+        act_name: "SPACE",
+        alt_name: "Q/E",
+        roll_name: "LSHIFT",
+    },
+    KeyMap {
+        up: KeyCode::Up,
+        left: KeyCode::Left,
+        down: KeyCode::Down,
+        right: KeyCode::Right,
+        act: KeyCode::RShift,
+        alt1: KeyCode::RControl,
+        alt2: KeyCode::Return,
+        roll: KeyCode::RAlt,
+        move_name: "ARROWS",
+        act_name: "RSHIFT",
+        alt_name: "RCTRL/ENTER",
+        roll_name: "RALT",
+    },
+    KeyMap {
+        up: KeyCode::I,
+        left: KeyCode::J,
+        down: KeyCode::K,
+        right: KeyCode::L,
+        act: KeyCode::O,
+        alt1: KeyCode::U,
+        alt2: KeyCode::P,
+        roll: KeyCode::H,
+        move_name: "IJKL",
+        act_name: "O",
+        alt_name: "U/P",
+        roll_name: "H",
+    },
+    KeyMap {
+        up: KeyCode::Numpad8,
+        left: KeyCode::Numpad4,
+        down: KeyCode::Numpad5,
+        right: KeyCode::Numpad6,
+        act: KeyCode::Numpad0,
+        alt1: KeyCode::Numpad7,
+        alt2: KeyCode::Numpad9,
+        roll: KeyCode::NumpadAdd,
+        move_name: "NUMPAD 8456",
+        act_name: "NUMPAD 0",
+        alt_name: "NUMPAD 7/9",
+        roll_name: "NUMPAD +",
+    },
+];
+
 pub fn input(
     handle: In<PlayerHandle>,
     keyboard_input: Res<bevy::input::Input<KeyCode>>,
     local_handles: Res<LocalHandles>,
+    rematch_request: Option<Res<RematchRequest>>,
+    mut surrender_held: Local<u32>,
 ) -> super::resources::Input {
-    let mut inp: u8 = 0;
+    let mut inp: u16 = 0;
 
-    if handle.0 == local_handles.handles[0] {
-        if keyboard_input.pressed(KeyCode::W) {
-            inp |= INPUT_UP;
-        }
-        if keyboard_input.pressed(KeyCode::A) {
-            inp |= INPUT_LEFT;
-        }
-        if keyboard_input.pressed(KeyCode::S) {
-            inp |= INPUT_DOWN;
-        }
-        if keyboard_input.pressed(KeyCode::D) {
-            inp |= INPUT_RIGHT;
-        }
-        if keyboard_input.pressed(KeyCode::Space) {
-            inp |= INPUT_ACT;
-        }
+    // online, the only local player uses the first keymap
+    let index = local_handles
+        .handles
+        .iter()
+        .position(|h| *h == handle.0)
+        .expect("No local handle for input.");
+    let keymap = &KEYMAPS[index];
+
+    if keyboard_input.pressed(keymap.up) {
+        inp |= INPUT_UP;
+    }
+    if keyboard_input.pressed(keymap.left) {
+        inp |= INPUT_LEFT;
+    }
+    if keyboard_input.pressed(keymap.down) {
+        inp |= INPUT_DOWN;
+    }
+    if keyboard_input.pressed(keymap.right) {
+        inp |= INPUT_RIGHT;
+    }
+    if keyboard_input.pressed(keymap.act) {
+        inp |= INPUT_ACT;
+    }
+    if keyboard_input.pressed(keymap.alt1) {
+        inp |= INPUT_ALT1;
+    }
+    if keyboard_input.pressed(keymap.alt2) {
+        inp |= INPUT_ALT2;
+    }
+    if keyboard_input.pressed(keymap.roll) {
+        inp |= INPUT_ROLL;
+    }
+
+    // locally, escape pauses the game instead
+    if local_handles.handles.len() == 1 && keyboard_input.pressed(KeyCode::Escape) {
+        *surrender_held += 1;
     } else {
-        if keyboard_input.pressed(KeyCode::Up) {
-            inp |= INPUT_UP;
-        }
-        if keyboard_input.pressed(KeyCode::Left) {
-            inp |= INPUT_LEFT;
-        }
-        if keyboard_input.pressed(KeyCode::Down) {
-            inp |= INPUT_DOWN;
-        }
-        if keyboard_input.pressed(KeyCode::Right) {
-            inp |= INPUT_RIGHT;
-        }
-        if keyboard_input.pressed(KeyCode::RShift) {
-            inp |= INPUT_ACT;
-        }
+        *surrender_held = 0;
+    }
+    if *surrender_held >= SURRENDER_HOLD_FRAMES {
+        inp |= INPUT_SURRENDER;
+    }
+
+    // stays set until the rematch starts, so predicting it is always correct
+    if rematch_request.is_some() {
+        inp |= INPUT_REMATCH;
     }
 
     super::resources::Input { inp }
@@ -98,27 +200,103 @@ pub fn on_round_end(state: Res<RoundState>) -> ShouldRun {
     }
 }
 
-pub fn setup_game(mut commands: Commands, misc_sprites: Res<MiscAssets>) {
-    commands.insert_resource(RoundState::InterludeStart);
-    commands.insert_resource(FrameCount::default());
-    commands.insert_resource(RoundData::default());
+pub fn on_match_end(state: Res<RoundState>) -> ShouldRun {
+    match *state {
+        RoundState::MatchEnd => ShouldRun::Yes,
+        _ => ShouldRun::No,
+    }
+}
+
+pub fn not_match_end(state: Res<RoundState>) -> ShouldRun {
+    match *state {
+        RoundState::MatchEnd => ShouldRun::No,
+        _ => ShouldRun::Yes,
+    }
+}
+
+pub fn setup_game(
+    mut commands: Commands,
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<Level>>,
+    font_assets: Res<FontAssets>,
+    rematch: Option<Res<Rematch>>,
+) {
+    // on a rematch, the rollback systems already reset the match in sync with the other players
+    if rematch.is_none() {
+        commands.insert_resource(RoundState::InterludeStart);
+        commands.insert_resource(FrameCount::default());
+        commands.insert_resource(RoundData::default());
+        commands.insert_resource(MatchStats::default());
+        commands.insert_resource(DefenderAmmo::default());
+    }
+    commands.remove_resource::<Rematch>();
+    commands.remove_resource::<RematchRequest>();
     let mut cam = OrthographicCameraBundle::new_2d();
     cam.orthographic_projection.scale = 1. / 2.; // Asset pixels are 2 times bigger than "device points"
     commands.spawn_bundle(cam).insert(GameEntity);
 
     commands
         .spawn_bundle(SpriteBundle {
-            texture: misc_sprites.background.clone(),
+            texture: levels
+                .get(&level_assets.default_level)
+                .expect("Level not loaded.")
+                .background_image
+                .clone(),
+            ..Default::default()
+        })
+        .insert(GameEntity);
+
+    for _ in 0..PREVIEW_DOTS {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: PREVIEW_COLOR,
+                    custom_size: Some(Vec2::splat(PREVIEW_DOT_SIZE)),
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(0., 0., 5.),
+                visibility: Visibility { is_visible: false },
+                ..Default::default()
+            })
+            .insert(TrajectoryDot)
+            .insert(GameEntity);
+    }
+
+    // only shows the rolled back DefenderAmmo, so it doesn't need to be rolled back itself
+    commands
+        .spawn_bundle(Text2dBundle {
+            transform: Transform::from_xyz(0., 0., 100.),
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: font_assets.default_font.clone(),
+                    font_size: 20.0,
+                    color: BUTTON_TEXT,
+                },
+                TextAlignment {
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Center,
+                },
+            ),
+            visibility: Visibility { is_visible: false },
             ..Default::default()
         })
+        .insert(AmmoUi)
         .insert(GameEntity);
 }
 
-pub fn setup_network_stats_ui(mut commands: Commands, font_assets: Res<FontAssets>) {
-    commands.insert_resource(ConnectionInfo {
-        status: ConnectionStatus::Synchronizing,
-        ping: 0,
-    });
+pub fn setup_network_stats_ui(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    rematch: Option<Res<Rematch>>,
+) {
+    // on a rematch, the session is already running
+    if rematch.is_none() {
+        commands.insert_resource(ConnectionInfo {
+            status: ConnectionStatus::Synchronizing,
+            ping: 0,
+        });
+    }
     commands
         .spawn_bundle(Text2dBundle {
             transform: Transform::from_xyz(
@@ -144,6 +322,14 @@ pub fn setup_network_stats_ui(mut commands: Commands, font_assets: Res<FontAsset
                             color: BUTTON_TEXT,
                         },
                     },
+                    TextSection {
+                        value: "\nHold ESC to surrender".to_owned(),
+                        style: TextStyle {
+                            font: font_assets.default_font.clone(),
+                            font_size: 20.0,
+                            color: BUTTON_TEXT,
+                        },
+                    },
                 ],
                 ..Default::default()
             },
@@ -161,6 +347,7 @@ pub fn handle_p2p_events(
         info!("GGRS Event: {:?}", event);
         match event {
             ggrs::GGRSEvent::Synchronized { .. } => con_info.status = ConnectionStatus::Running,
+            // the disconnected player forfeits, see check_forfeit
             ggrs::GGRSEvent::Disconnected { .. } => {
                 con_info.status = ConnectionStatus::Disconnected
             }
@@ -178,8 +365,9 @@ pub fn update_connection_info(
     mut con_info: ResMut<ConnectionInfo>,
     session: ResMut<P2PSession<GGRSConfig>>,
     local_handles: Res<LocalHandles>,
+    settings: Res<MatchSettings>,
 ) {
-    for handle in 0..NUM_PLAYERS {
+    for handle in 0..settings.num_players {
         if local_handles.handles.contains(&handle) {
             continue;
         }
@@ -201,26 +389,131 @@ pub fn update_connection_display(
 
 pub fn update_screen_timer(
     frame_count: Res<FrameCount>,
+    round_data: Res<RoundData>,
+    settings: Res<MatchSettings>,
     mut timer: Query<&mut Text, With<ScreenTimer>>,
 ) {
-    let remaining_secs = (ROUND_LENGTH - frame_count.frame) / 60;
+    let remaining_secs = round_data
+        .round_length(&settings)
+        .saturating_sub(frame_count.frame)
+        / 60;
 
     for mut text in timer.iter_mut() {
         text.sections[0].value = remaining_secs.to_string();
     }
 }
 
-pub fn cleanup_game(query: Query<Entity, With<GameEntity>>, mut commands: Commands) {
+pub fn update_ammo_display(
+    ammo: Res<DefenderAmmo>,
+    defenders: Query<&Transform, With<Defender>>,
+    mut ammo_text: Query<
+        (&mut Text, &mut Transform, &mut Visibility),
+        (With<AmmoUi>, Without<Defender>),
+    >,
+) {
+    for (mut text, mut t, mut visibility) in ammo_text.iter_mut() {
+        // the fortress can roll around, the ammo stays above it. Between rounds there is no fortress
+        let t_def = defenders.iter().next();
+        visibility.is_visible = t_def.is_some();
+        if let Some(t_def) = t_def {
+            t.translation.x = t_def.translation.x;
+            t.translation.y = t_def.translation.y + DEFENDER_SIZE / 2. + 10.;
+        }
+        text.sections[0].value = format!("CAKES {}/{}", ammo.cakes, MAX_AMMO);
+        // greyed out while the fortress can't fire
+        let alpha = if ammo.cooldown > 0 { 0.5 } else { 1. };
+        text.sections[0].style.color.set_a(alpha);
+    }
+}
+
+// a dotted arc from the cannon, only the fortress player gets to see it. It bends through fans
+// like the cake will, it just can't know about janitors and platforms in the way.
+pub fn update_trajectory_preview(
+    settings: Res<MatchSettings>,
+    gravity: Res<Gravity>,
+    local_handles: Res<LocalHandles>,
+    defenders: Query<(&Transform, &Defender, &DefenderState, &FacingDirection)>,
+    crosshairs: Query<&Transform, With<Crosshair>>,
+    zones: Query<(&Pos, &ForceZone)>,
+    mut dots: Query<
+        (&mut Transform, &mut Visibility),
+        (With<TrajectoryDot>, Without<Defender>, Without<Crosshair>),
+    >,
+) {
+    let local_defender = defenders
+        .iter()
+        .find(|(_, defender, ..)| local_handles.handles.contains(&defender.handle));
+    let ((t_def, defender, state, face_dir), t_cross) =
+        match (local_defender, crosshairs.iter().next()) {
+            (Some(defender), Some(t_cross)) => (defender, t_cross),
+            _ => {
+                for (_, mut visibility) in dots.iter_mut() {
+                    visibility.is_visible = false;
+                }
+                return;
+            }
+        };
+
+    // show the power the shot would have if it was released now
+    let power = match *state {
+        DefenderState::Idle(_) => DefenderState::charge_power(0),
+        DefenderState::Charge(f) => DefenderState::charge_power(f),
+        DefenderState::Fire(_) => defender.power,
+    };
+    let muzzle = cake_muzzle(t_def, face_dir);
+    let vel = launch_velocity(
+        settings.aim_mode,
+        defender.cake_type,
+        power,
+        muzzle,
+        t_cross.translation.truncate(),
+        gravity.0,
+    );
+    // fans push every particle of the cake, the same way integrate does
+    let mass = defender.cake_type.params().particle_mass();
+    let accel = |pos: Vec2| {
+        zones
+            .iter()
+            .filter(|(zone_pos, zone)| zone.contains(zone_pos.0, pos))
+            .fold(gravity.0, |sum, (_, zone)| sum + zone.force / mass)
+    };
+    let points = ballistics::trajectory_with(muzzle, vel, accel).step_by(PREVIEW_DOT_STEPS);
+    for ((mut t, mut visibility), point) in dots.iter_mut().zip(points) {
+        t.translation.x = point.x;
+        t.translation.y = point.y;
+        // nothing to see below the screen
+        visibility.is_visible = point.y > -SCREEN_Y / 4.;
+    }
+}
+
+// leaving a round early skips cleanup_round, so the round world goes here as well
+pub fn cleanup_game(
+    query: Query<Entity, Or<(With<RoundEntity>, With<Interlude>, With<GameEntity>)>>,
+    mut commands: Commands,
+) {
+    for e in query.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+}
+
+// online, the session outlives the round, so players can ask for a rematch on the win screen
+pub fn cleanup_session(mut commands: Commands, rematch: Option<Res<Rematch>>) {
+    if rematch.is_some() {
+        return;
+    }
+
+    commands.remove_resource::<RoundState>();
     commands.remove_resource::<RoundData>();
+    commands.remove_resource::<MatchStats>();
+    commands.remove_resource::<DefenderAmmo>();
+    commands.remove_resource::<MatchRng>();
     commands.remove_resource::<FrameCount>();
     commands.remove_resource::<LocalHandles>();
     commands.remove_resource::<P2PSession<GGRSConfig>>();
+    commands.remove_resource::<SyncTestSession<GGRSConfig>>();
     commands.remove_resource::<SessionType>();
     commands.remove_resource::<ConnectionInfo>();
-
-    for e in query.iter() {
-        commands.entity(e).despawn_recursive();
-    }
+    commands.remove_resource::<RematchRequest>();
 }
 
 pub fn update_attacker_sprite(
@@ -241,6 +534,11 @@ pub fn update_attacker_sprite(
             AttackerState::Land(_) => *atlas_handle = sprites.janitor_land.clone(),
             AttackerState::Walk(_) => *atlas_handle = sprites.janitor_walk.clone(),
             AttackerState::Hit(_) => *atlas_handle = sprites.janitor_hit.clone(),
+            AttackerState::Bumped(_) => *atlas_handle = sprites.janitor_fall.clone(),
+            AttackerState::Scrub(_) => *atlas_handle = sprites.janitor_walk.clone(),
+            AttackerState::Swipe(_) => *atlas_handle = sprites.janitor_land.clone(),
+            AttackerState::Dash(_) => *atlas_handle = sprites.janitor_jump.clone(),
+            AttackerState::Block(_) => *atlas_handle = sprites.janitor_idle.clone(),
         }
 
         let texture_atlas = texture_atlases
@@ -254,6 +552,28 @@ pub fn update_attacker_sprite(
     }
 }
 
+// splats fade out while they are being scrubbed
+pub fn update_splat_sprite(mut query: Query<(&mut Sprite, &Splat)>) {
+    for (mut sprite, splat) in query.iter_mut() {
+        let progress = splat.dirt as f32 / splat.max_dirt.max(1) as f32;
+        sprite
+            .color
+            .set_a(MIN_SPLAT_ALPHA + (1. - MIN_SPLAT_ALPHA) * progress);
+    }
+}
+
+// the crosshair shows which cake the fortress fires next
+pub fn update_crosshair_sprite(
+    defenders: Query<&Defender>,
+    mut crosshairs: Query<&mut Sprite, With<Crosshair>>,
+) {
+    for defender in defenders.iter() {
+        for mut sprite in crosshairs.iter_mut() {
+            sprite.color = defender.cake_type.params().color;
+        }
+    }
+}
+
 pub fn update_defender_sprite(
     mut query: Query<(
         &mut TextureAtlasSprite,
@@ -267,16 +587,84 @@ pub fn update_defender_sprite(
     for (mut sprite, mut atlas_handle, face_dir, state) in query.iter_mut() {
         match *state {
             DefenderState::Idle(_) => *atlas_handle = sprites.fortress_idle.clone(),
-            DefenderState::Fire(_) => *atlas_handle = sprites.fortress_fire.clone(),
+            DefenderState::Charge(_) | DefenderState::Fire(_) => {
+                *atlas_handle = sprites.fortress_fire.clone()
+            }
         }
 
         let texture_atlas = texture_atlases
             .get(atlas_handle.as_ref())
             .expect("TextureAtlas not found.");
-        sprite.index = (state.get_frame() / FRAMES_PER_SPRITE) % texture_atlas.textures.len();
+        sprite.index = match *state {
+            // hold the wind-up frame while charging
+            DefenderState::Charge(_) => 0,
+            _ => (state.get_frame() / FRAMES_PER_SPRITE) % texture_atlas.textures.len(),
+        };
+        // unlike the janitor, the fortress art faces left
         sprite.flip_x = match *face_dir {
-            FacingDirection::Left => true,
-            FacingDirection::Right => false,
+            FacingDirection::Left => false,
+            FacingDirection::Right => true,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AppState;
+
+    // stands in for the rollback systems spawning the first round
+    fn spawn_round(mut commands: Commands) {
+        commands.spawn().insert(GameEntity);
+        commands.spawn().insert(Interlude);
+        commands
+            .spawn()
+            .insert(Attacker { handle: 1 })
+            .insert(RoundEntity);
+        commands
+            .spawn()
+            .insert(Defender {
+                handle: 0,
+                ..Default::default()
+            })
+            .insert(RoundEntity);
+        commands
+            .spawn()
+            .insert(Splat {
+                dirt: 1,
+                max_dirt: 1,
+            })
+            .insert(RoundEntity);
+    }
+
+    fn count<T: Component>(app: &mut App) -> usize {
+        app.world.query::<&T>().iter(&app.world).count()
+    }
+
+    fn set_state(app: &mut App, f: impl FnOnce(&mut State<AppState>)) {
+        f(&mut app.world.get_resource_mut::<State<AppState>>().unwrap());
+        app.update();
+    }
+
+    #[test]
+    fn quitting_from_pause_despawns_the_round() {
+        let mut app = App::new();
+        app.add_state(AppState::RoundLocal)
+            .add_system_set(SystemSet::on_enter(AppState::RoundLocal).with_system(spawn_round))
+            .add_system_set(SystemSet::on_exit(AppState::RoundLocal).with_system(cleanup_game));
+        app.update();
+
+        // pause, quit to the menu and start a new match, like the pause menu buttons do
+        set_state(&mut app, |s| s.push(AppState::Paused).unwrap());
+        set_state(&mut app, |s| s.replace(AppState::MenuMain).unwrap());
+        assert_eq!(count::<RoundEntity>(&mut app), 0);
+        assert_eq!(count::<Interlude>(&mut app), 0);
+        assert_eq!(count::<GameEntity>(&mut app), 0);
+        set_state(&mut app, |s| s.set(AppState::RoundLocal).unwrap());
+
+        assert_eq!(count::<Attacker>(&mut app), 1);
+        assert_eq!(count::<Defender>(&mut app), 1);
+        assert_eq!(count::<Splat>(&mut app), 1);
+        assert_eq!(count::<Interlude>(&mut app), 1);
+    }
+}